@@ -0,0 +1,341 @@
+use std::collections::{HashMap, VecDeque};
+use std::f32::consts::{PI, TAU};
+
+use macroquad::prelude as mq;
+use maze_wars_protocol::{GameState, Player, PlayerId, Position, TILE_SIZE};
+
+use crate::ScalingInfo;
+
+/// How far behind the newest state other players are shown, in seconds, so
+/// there is almost always a state on either side to blend between.
+const INTERPOLATION_DELAY: f64 = 0.1;
+
+/// Longest a player is carried on past the newest state when states stop
+/// coming, in seconds. After that they wait where they were last headed.
+const MAX_EXTRAPOLATION: f64 = 0.1;
+
+/// Share of the gap to where the playback clock should be that it closes on
+/// every state, which smooths out states arriving early or late.
+const CLOCK_CORRECTION: f64 = 0.1;
+
+/// A playback clock further off than this, in seconds, jumps straight to
+/// where it should be, such as after the window stalled.
+const MAX_CLOCK_ERROR: f64 = 0.25;
+
+/// States kept at most, a couple of seconds' worth at any tick rate.
+const MAX_SNAPSHOTS: usize = 64;
+
+/// Players that moved further than this between two states, in tiles, were
+/// respawned or put in a new maze and are not slid across the map.
+const TELEPORT_DISTANCE: f32 = 2.0;
+
+/// Where everyone stood in one state from the server.
+struct Snapshot {
+    /// Server time of the state, in seconds.
+    time: f64,
+    players: HashMap<PlayerId, (Position, f32)>,
+}
+
+/// Other players' positions and facing, played back a little behind the
+/// states from the server instead of jumping to each one as it arrives.
+/// States are placed in time by the server tick they were made on, so how
+/// often the server sends them and how unevenly they arrive doesn't show.
+pub struct Interpolation {
+    tick_length: f64,
+    snapshots: VecDeque<Snapshot>,
+    /// Server time being shown, in seconds; `None` until the first state.
+    clock: Option<f64>,
+}
+
+impl Interpolation {
+    pub fn new(tick_rate: u32) -> Self {
+        Self {
+            tick_length: 1.0 / tick_rate as f64,
+            snapshots: VecDeque::new(),
+            clock: None,
+        }
+    }
+
+    /// Adds a state from the server to the buffer and steers the playback
+    /// clock towards showing it `INTERPOLATION_DELAY` from now.
+    pub fn push(&mut self, state: &GameState) {
+        let time = state.tick as f64 * self.tick_length;
+        if self
+            .snapshots
+            .back()
+            .is_some_and(|newest| time <= newest.time)
+        {
+            return;
+        }
+        if self.snapshots.len() == MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(Snapshot {
+            time,
+            players: state
+                .players
+                .iter()
+                .map(|p| (p.id, (p.pos, p.angle)))
+                .collect(),
+        });
+
+        let target = time - INTERPOLATION_DELAY;
+        self.clock = Some(match self.clock {
+            Some(clock) if (target - clock).abs() <= MAX_CLOCK_ERROR => {
+                clock + (target - clock) * CLOCK_CORRECTION
+            }
+            _ => target,
+        });
+    }
+
+    /// Moves the playback clock on by `delta` seconds and forgets the states
+    /// it has left behind.
+    pub fn advance(&mut self, delta: f32) {
+        let Some(clock) = self.clock.as_mut() else {
+            return;
+        };
+        *clock += delta as f64;
+        // the newest state before the clock is still needed to blend from,
+        // and the two newest to carry players on past them
+        while self.snapshots.len() > 2 && self.snapshots[1].time <= *clock {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// `players` moved to where they are at the playback clock. Our own
+    /// player is left alone, prediction places it, and so is anyone the
+    /// buffer hasn't seen yet.
+    pub fn apply(&self, players: &mut [Player], own_id: PlayerId) {
+        for player in players.iter_mut().filter(|p| p.id != own_id) {
+            if let Some((pos, angle)) = self.sample(player.id) {
+                player.pos = pos;
+                player.angle = angle;
+            }
+        }
+    }
+
+    /// Where player `id` is at the playback clock and which way they face.
+    fn sample(&self, id: PlayerId) -> Option<(Position, f32)> {
+        let clock = self.clock?;
+        let newest = self.snapshots.back()?;
+        let after = self.snapshots.iter().position(|s| s.time > clock);
+
+        match after {
+            // waiting on a state: carry on the way they were going, for a while
+            None => {
+                let &(pos, angle) = newest.players.get(&id)?;
+                let previous = self.snapshots.iter().rev().nth(1);
+                let Some(&(from, _)) = previous.and_then(|s| s.players.get(&id)) else {
+                    return Some((pos, angle));
+                };
+                if teleported(from, pos) {
+                    return Some((pos, angle));
+                }
+                let previous_time = previous.map_or(newest.time, |s| s.time);
+                let ahead = (clock - newest.time).min(MAX_EXTRAPOLATION);
+                let t = (ahead / (newest.time - previous_time)) as f32;
+                let x = pos.x + (pos.x - from.x) * t;
+                let y = pos.y + (pos.y - from.y) * t;
+                Some((Position::new(x, y), angle))
+            }
+            // the clock is before every state we have, show the oldest
+            Some(0) => self.snapshots[0].players.get(&id).copied(),
+            Some(after) => {
+                let (from, to) = (&self.snapshots[after - 1], &self.snapshots[after]);
+                let Some(&(to_pos, to_angle)) = to.players.get(&id) else {
+                    return from.players.get(&id).copied();
+                };
+                let Some(&(from_pos, from_angle)) = from.players.get(&id) else {
+                    return Some((to_pos, to_angle));
+                };
+                if teleported(from_pos, to_pos) {
+                    return Some((from_pos, from_angle));
+                }
+                let t = ((clock - from.time) / (to.time - from.time)) as f32;
+                let x = from_pos.x + (to_pos.x - from_pos.x) * t;
+                let y = from_pos.y + (to_pos.y - from_pos.y) * t;
+                // turn the short way round
+                let turn = (to_angle - from_angle + PI).rem_euclid(TAU) - PI;
+                let angle = (from_angle + turn * t).rem_euclid(TAU);
+                Some((Position::new(x, y), angle))
+            }
+        }
+    }
+
+    /// Buffered states the clock hasn't reached yet, and how far ahead of
+    /// it the newest one is in seconds; negative while extrapolating.
+    fn depth(&self) -> (usize, f64) {
+        match (self.clock, self.snapshots.back()) {
+            (Some(clock), Some(newest)) => {
+                let ahead = self.snapshots.iter().filter(|s| s.time > clock).count();
+                (ahead, newest.time - clock)
+            }
+            _ => (0, 0.0),
+        }
+    }
+}
+
+fn teleported(from: Position, to: Position) -> bool {
+    (to.x - from.x).hypot(to.y - from.y) > TELEPORT_DISTANCE * TILE_SIZE
+}
+
+/// How full the buffer of states is, under the player's stats in the top
+/// left corner. Toggled with F3.
+pub fn draw_debug_overlay(interpolation: &Interpolation, scaling_info: &ScalingInfo) {
+    let (ahead, lead) = interpolation.depth();
+    let lines = [
+        format!("Buffered: {} states", ahead),
+        format!("Lead: {:.0} ms", lead * 1000.0),
+        format!("Delay: {:.0} ms", INTERPOLATION_DELAY * 1000.0),
+    ];
+
+    let x = scaling_info.offset.x + 1.0;
+    let y = scaling_info.offset.y + 70.0;
+    mq::draw_rectangle(
+        x,
+        y,
+        140.0,
+        5.0 + 15.0 * lines.len() as f32,
+        mq::Color::new(1.0, 1.0, 1.0, 0.5),
+    );
+    // out of states to blend between, players are being guessed at
+    let color = if lead < 0.0 { mq::RED } else { mq::BLUE };
+    for (i, line) in lines.iter().enumerate() {
+        mq::draw_text(line, x + 4.0, y + 15.0 * (i as f32 + 1.0), 20.0, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maze_wars_protocol::testing::{at, maze, state};
+
+    /// A tick lasts a tenth of a second.
+    const TICK_RATE: u32 = 10;
+
+    /// The state of `tick`, with each of `players` as (id, column, angle)
+    /// on the same row.
+    fn state_at(tick: u32, players: &[(PlayerId, f32, f32)]) -> GameState {
+        let players = players
+            .iter()
+            .map(|&(id, x, angle)| {
+                let mut player = Player::new(at(x, 1.5), id, format!("player{id}"));
+                player.angle = angle;
+                player
+            })
+            .collect();
+        state(maze(&["###", "#.#", "###"]), tick, players)
+    }
+
+    /// Interpolation of player 1 walking along the row, one column per
+    /// entry of `xs`, a tick apart from tick 10 on, shown at `clock`.
+    fn walking(xs: &[f32], clock: f64) -> Interpolation {
+        let mut interpolation = Interpolation::new(TICK_RATE);
+        for (tick, &x) in (10..).zip(xs) {
+            interpolation.push(&state_at(tick, &[(1, x, 0.0)]));
+        }
+        interpolation.clock = Some(clock);
+        interpolation
+    }
+
+    fn x_of(interpolation: &Interpolation, id: PlayerId) -> f32 {
+        interpolation.sample(id).unwrap().0.x / TILE_SIZE
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn states_no_newer_than_the_last_are_ignored() {
+        let mut interpolation = Interpolation::new(TICK_RATE);
+        interpolation.push(&state_at(10, &[]));
+        interpolation.push(&state_at(5, &[]));
+        interpolation.push(&state_at(10, &[]));
+        assert_eq!(interpolation.snapshots.len(), 1);
+    }
+
+    #[test]
+    fn the_clock_is_steered_a_little_and_snaps_when_far_off() {
+        let mut interpolation = Interpolation::new(TICK_RATE);
+        interpolation.push(&state_at(10, &[]));
+        assert_close(interpolation.clock.unwrap(), 1.0 - INTERPOLATION_DELAY);
+        // a tick later the clock should be at 1.0, it goes a tenth of the way
+        interpolation.push(&state_at(11, &[]));
+        assert_close(interpolation.clock.unwrap(), 0.9 + 0.1 * CLOCK_CORRECTION);
+        // a second further on is more than `MAX_CLOCK_ERROR` off
+        interpolation.push(&state_at(21, &[]));
+        assert_close(interpolation.clock.unwrap(), 2.1 - INTERPOLATION_DELAY);
+    }
+
+    #[test]
+    fn advancing_forgets_states_the_clock_has_passed() {
+        let mut interpolation = walking(&[1.0, 1.0, 1.0, 1.0], 1.0);
+        interpolation.advance(0.15);
+        // the state at 1.1 is still needed to blend from
+        let times: Vec<f64> = interpolation.snapshots.iter().map(|s| s.time).collect();
+        assert_eq!(times.len(), 3);
+        assert_close(times[0], 1.1);
+        // past the newest, the two newest are kept to carry players on
+        interpolation.advance(1.0);
+        assert_eq!(interpolation.snapshots.len(), 2);
+    }
+
+    #[test]
+    fn players_are_blended_between_the_states_either_side() {
+        let interpolation = walking(&[2.0, 3.0], 1.025);
+        assert!((x_of(&interpolation, 1) - 2.25).abs() < 1e-3);
+        // before the oldest state, it is shown as it was
+        let early = walking(&[2.0, 3.0], 0.5);
+        assert_eq!(x_of(&early, 1), 2.0);
+    }
+
+    #[test]
+    fn our_own_player_and_strangers_are_left_alone() {
+        let mut interpolation = Interpolation::new(TICK_RATE);
+        interpolation.push(&state_at(10, &[(0, 2.0, 0.0), (1, 2.0, 0.0)]));
+        interpolation.push(&state_at(11, &[(0, 3.0, 0.0), (1, 3.0, 0.0)]));
+        interpolation.clock = Some(1.05);
+        let mut players = state_at(11, &[(0, 5.0, 0.0), (1, 5.0, 0.0), (2, 5.0, 0.0)]).players;
+        interpolation.apply(&mut players, 0);
+        let xs: Vec<f32> = players.iter().map(|p| p.pos.x / TILE_SIZE).collect();
+        assert_eq!(xs[0], 5.0);
+        assert!((xs[1] - 2.5).abs() < 1e-3);
+        assert_eq!(xs[2], 5.0);
+    }
+
+    #[test]
+    fn players_are_carried_on_for_a_while_when_states_stop() {
+        // half a tile a tick, for no more than `MAX_EXTRAPOLATION` past the newest
+        let interpolation = walking(&[2.0, 2.5], 1.15);
+        assert!((x_of(&interpolation, 1) - 2.75).abs() < 1e-3);
+        let later = walking(&[2.0, 2.5], 3.0);
+        assert!((x_of(&later, 1) - 3.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn a_player_who_teleported_is_not_slid_across() {
+        let jump = 1.0 + TELEPORT_DISTANCE * 2.0;
+        let between = walking(&[1.0, jump], 1.05);
+        assert_eq!(x_of(&between, 1), 1.0);
+        let after = walking(&[1.0, jump], 1.15);
+        assert_eq!(x_of(&after, 1), jump);
+    }
+
+    #[test]
+    fn turns_go_the_short_way_round() {
+        let mut interpolation = Interpolation::new(TICK_RATE);
+        interpolation.push(&state_at(10, &[(1, 2.0, TAU - 0.2)]));
+        interpolation.push(&state_at(11, &[(1, 2.0, 0.2)]));
+        interpolation.clock = Some(1.025);
+        let (_, angle) = interpolation.sample(1).unwrap();
+        // a quarter of the way from -0.2 to 0.2
+        let off = (angle - (TAU - 0.1)).rem_euclid(TAU);
+        assert!(off.min(TAU - off) < 1e-4, "{}", angle);
+    }
+}
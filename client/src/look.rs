@@ -0,0 +1,68 @@
+use std::f32::consts::{PI, TAU};
+
+use macroquad::prelude as mq;
+use maze_wars_protocol::{Player, MAX_LOOK_VERTICAL};
+
+/// Radians a second the arrow keys turn the player.
+const KEY_TURN_SPEED: f32 = PI;
+const KEY_LOOK_UP_SPEED: f32 = PI / 4.0;
+
+/// Radians a pixel of mouse movement turns the player at sensitivity 1.
+const MOUSE_RADIANS_PER_PIXEL: f32 = 0.003;
+
+/// Where the player is looking. Turned here, by the arrow keys and the
+/// mouse, so the view answers straight away; the server is told whenever it
+/// changes.
+pub struct Look {
+    pub angle: f32,
+    pub vertical: f32,
+    sensitivity: f32,
+    /// Mouse position last frame while the pointer is grabbed.
+    last_mouse: Option<mq::Vec2>,
+}
+
+impl Look {
+    pub fn new(player: &Player, sensitivity: f32) -> Self {
+        Self {
+            angle: player.angle,
+            vertical: player.angle_vertical,
+            sensitivity,
+            last_mouse: None,
+        }
+    }
+
+    /// Captures the mouse so moving it turns the player, or lets it go.
+    pub fn grab_pointer(&mut self, grab: bool) {
+        mq::set_cursor_grab(grab);
+        mq::show_mouse(!grab);
+        self.last_mouse = grab.then(|| mq::mouse_position().into());
+    }
+
+    pub fn is_pointer_grabbed(&self) -> bool {
+        self.last_mouse.is_some()
+    }
+
+    /// Turns by this frame's arrow keys and mouse movement, `delta` seconds
+    /// after the last frame. Returns whether the look changed.
+    pub fn update(&mut self, delta: f32) -> bool {
+        let (angle, vertical) = (self.angle, self.vertical);
+
+        let key = |key| mq::is_key_down(key) as i32 as f32;
+        self.angle += (key(mq::KeyCode::Right) - key(mq::KeyCode::Left)) * KEY_TURN_SPEED * delta;
+        self.vertical +=
+            (key(mq::KeyCode::Up) - key(mq::KeyCode::Down)) * KEY_LOOK_UP_SPEED * delta;
+
+        if let Some(last_mouse) = self.last_mouse {
+            let mouse: mq::Vec2 = mq::mouse_position().into();
+            let moved = (mouse - last_mouse) * MOUSE_RADIANS_PER_PIXEL * self.sensitivity;
+            self.angle += moved.x;
+            self.vertical -= moved.y;
+            self.last_mouse = Some(mouse);
+        }
+
+        // the same limits the server holds us to
+        self.angle = self.angle.rem_euclid(TAU);
+        self.vertical = self.vertical.clamp(-MAX_LOOK_VERTICAL, MAX_LOOK_VERTICAL);
+        (self.angle, self.vertical) != (angle, vertical)
+    }
+}
@@ -1,23 +1,34 @@
 use macroquad::prelude as mq;
+use maze_wars_protocol::{
+    decode, encode, Action, ClientMessage, Direction, EventKind, GameEvent, GameState, JoinRequest,
+    JoinResponse, LeaveReason, Maze, MoveKeys, Player, PlayerId, PlayerUpdate, Position,
+    RoundPhase, ServerMessage, SnapshotHistory, Weapon, MAX_DATAGRAM_SIZE, TILE_SIZE,
+};
 use serde::{Deserialize, Serialize};
 use std::sync::mpsc::{self, Receiver, Sender};
-use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 use tokio::net::UdpSocket;
 use tokio::runtime::Runtime;
+mod interpolation;
+mod look;
 mod menu;
+mod prediction;
+mod scoreboard;
+mod session;
 mod shared;
+mod sprites;
+use interpolation::{draw_debug_overlay, Interpolation};
+use look::Look;
+use prediction::Prediction;
+use scoreboard::draw_scoreboard;
+use session::{load_session, save_session};
 use shared::GameSessionInfo;
+use sprites::{draw_name_tags, draw_player_sprites, player_frames};
 
 const WINDOW_WIDTH: u32 = 1024;
 const WINDOW_HEIGHT: u32 = 512;
 
-const MAZE_WIDTH: usize = 24;
-const MAZE_HEIGHT: usize = MAZE_WIDTH; // To ensure a square map
-
-const TILE_SIZE: f32 = 64.0 / 3.0;
-
 const NUM_RAYS: u32 = 512;
 const RAYS_PER_SECOND: f32 = NUM_RAYS as f32 / 2.0;
 
@@ -25,88 +36,49 @@ const FOV: f32 = std::f32::consts::PI / 2.0;
 
 const VIEW_DISTANCE: f32 = 7.0 * TILE_SIZE;
 
+/// Furthest a view ray looks for a wall.
+const MAX_RAY_DISTANCE: f32 = 100.0 * TILE_SIZE;
+
 const NUM_TEXTURES: i32 = 3;
 
+/// Kills listed at once; older ones scroll off the bottom of the feed.
+const KILL_FEED_LINES: usize = 5;
+
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
 const BACKGROUND_COLOR: mq::Color = mq::Color::new(73.0 / 255.0, 1.0, 1.0, 1.0);
 const GROUND_COLOR: mq::Color = mq::Color::new(36.0 / 255.0, 219.0 / 255.0, 0.0, 1.0);
 const NORD_COLOR: mq::Color = mq::Color::new(46.0 / 255.0, 52.0 / 255.0, 64.0 / 255.0, 1.0);
 
-#[derive(Serialize, Deserialize, Clone)]
-struct PlayerUpdate {
-    id: u8,
-    action: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Maze {
-    pub width: usize,
-    pub height: usize,
-    pub layout: Vec<u8>,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct GameState {
-    players: Vec<Player>,
-    maze: Vec<u8>,
-    new_round_state: bool,
-    winner: String,
-}
-
-#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
-struct Position {
-    x: f32,
-    y: f32,
-}
-
-type Direction = Position;
-
-impl Position {
-    fn new(x: f32, y: f32) -> Self {
-        Self { x, y }
-    }
-}
 #[derive(Clone, Copy, Serialize, Deserialize)]
 struct Ray {
     pos: Position,
     angle: f32,
     direction: Direction,
 }
-#[derive(Clone, Serialize, Deserialize, Debug)]
-struct Player {
-    id: u8,
-    pos: Position,
-    direction: Direction,
-    angle: f32,          // in radians
-    angle_vertical: f32, // in radians
-    action: String,
-    score: u32,
+trait PlayerView {
+    fn draw(&self, scaling_info: &ScalingInfo, map_scale: f32, color: mq::Color);
+    fn cast_rays(&self, maze: &Maze, num_rays: u32) -> Vec<(Ray, Option<RayHit>)>;
 }
 
-impl Player {
-    fn draw(&self, scaling_info: &ScalingInfo) {
-        mq::draw_circle(
-            scaling_info.offset.x + self.pos.x * scaling_info.width / WINDOW_WIDTH as f32,
-            scaling_info.offset.y + self.pos.y * scaling_info.height / WINDOW_HEIGHT as f32,
-            8.0,
-            mq::YELLOW,
-        );
+impl PlayerView for Player {
+    fn draw(&self, scaling_info: &ScalingInfo, map_scale: f32, color: mq::Color) {
+        let x = scaling_info.offset.x + self.pos.x / TILE_SIZE * map_scale;
+        let y = scaling_info.offset.y + self.pos.y / TILE_SIZE * map_scale;
+        mq::draw_circle(x, y, 8.0, color);
 
         // Draw the line representing the player's direction
         mq::draw_line(
-            scaling_info.offset.x + self.pos.x * scaling_info.width / WINDOW_WIDTH as f32,
-            scaling_info.offset.y + self.pos.y * scaling_info.height / WINDOW_HEIGHT as f32,
-            scaling_info.offset.x
-                + self.pos.x * scaling_info.width / WINDOW_WIDTH as f32
-                + self.angle.cos() * 20.0,
-            scaling_info.offset.y
-                + self.pos.y * scaling_info.height / WINDOW_HEIGHT as f32
-                + self.angle.sin() * 20.0,
+            x,
+            y,
+            x + self.angle.cos() * 20.0,
+            y + self.angle.sin() * 20.0,
             3.0,
-            mq::YELLOW,
+            color,
         );
     }
 
-    fn cast_rays(&self, maze: &mut [u8], num_rays: u32) -> Vec<(Ray, Option<RayHit>)> {
+    fn cast_rays(&self, maze: &Maze, num_rays: u32) -> Vec<(Ray, Option<RayHit>)> {
         let rotation_matrix = mq::Mat2::from_angle(self.angle);
 
         (0..num_rays)
@@ -148,11 +120,62 @@ impl Lerp for mq::Color {
     }
 }
 
-fn draw_map(maze: &[u8], scaling_info: &ScalingInfo) {
-    let scaled_size = scaling_info.width / (MAZE_WIDTH as f32 * 2.0);
-    for y in 0..MAZE_HEIGHT {
-        for x in 0..MAZE_WIDTH {
-            let wall = maze[y * MAZE_WIDTH + x];
+/// Size of a maze tile on the minimap, which fills the left half of the
+/// window whatever the maze's shape.
+fn map_scale(maze: &Maze, scaling_info: &ScalingInfo) -> f32 {
+    (scaling_info.width / 2.0 / maze.width as f32).min(scaling_info.height / maze.height as f32)
+}
+
+fn weapon_name(weapon: Weapon) -> &'static str {
+    match weapon {
+        Weapon::Blaster => "blaster",
+    }
+}
+
+/// The latest kills in the top right corner, newest on top. Kills the player
+/// made or suffered stand out.
+fn draw_kill_feed(events: &[GameEvent], own_id: PlayerId, scaling_info: &ScalingInfo) {
+    let kills = events
+        .iter()
+        .rev()
+        .filter_map(|event| match &event.kind {
+            EventKind::Kill {
+                killer,
+                killer_name,
+                victim,
+                victim_name,
+                weapon,
+            } => Some((*killer, killer_name, *victim, victim_name, *weapon)),
+            EventKind::PlayerLeft { .. } => None,
+        })
+        .take(KILL_FEED_LINES);
+    for (i, (killer, killer_name, victim, victim_name, weapon)) in kills.enumerate() {
+        let text = format!("{} [{}] {}", killer_name, weapon_name(weapon), victim_name);
+        let color = if victim == own_id {
+            mq::RED
+        } else if killer == own_id {
+            mq::GREEN
+        } else {
+            mq::WHITE
+        };
+        let size = mq::measure_text(&text, None, 20, 1.0);
+        let x = scaling_info.offset.x + scaling_info.width - size.width - 10.0;
+        let y = scaling_info.offset.y + 20.0 + 22.0 * i as f32;
+        mq::draw_rectangle(
+            x - 4.0,
+            y - 15.0,
+            size.width + 8.0,
+            20.0,
+            mq::Color::new(0.0, 0.0, 0.0, 0.5),
+        );
+        mq::draw_text(&text, x, y, 20.0, color);
+    }
+}
+
+fn draw_map(maze: &Maze, scaling_info: &ScalingInfo, scaled_size: f32) {
+    for y in 0..maze.height {
+        for x in 0..maze.width {
+            let wall = maze.tiles[y * maze.width + x];
             let color = match wall {
                 1 => mq::BLACK,
                 2 => mq::RED,
@@ -256,84 +279,27 @@ impl Ray {
             direction,
         }
     }
-    fn cast_ray(&self, maze: &mut [u8]) -> (Ray, Option<RayHit>) {
-        // DDA algorithm
-        let x = self.pos.x / TILE_SIZE; // (0.0, 8.0)
-        let y = self.pos.y / TILE_SIZE; // (0.0, 8.0)
-        let ray_start = mq::Vec2::new(x, y);
-
+    fn cast_ray(&self, maze: &Maze) -> (Ray, Option<RayHit>) {
         let ray_dir = mq::Vec2::new(self.direction.x, self.direction.y).normalize();
-
-        let ray_unit_step_size = mq::Vec2::new(
-            (1.0 + (ray_dir.y / ray_dir.x).powi(2)).sqrt(),
-            (1.0 + (ray_dir.x / ray_dir.y).powi(2)).sqrt(),
-        );
-        let mut map_check = ray_start.floor();
-        let mut ray_length_1d = mq::Vec2::ZERO;
-        let mut step = mq::Vec2::ZERO;
-
-        if ray_dir.x < 0.0 {
-            step.x = -1.0;
-            ray_length_1d.x = (x - map_check.x) * ray_unit_step_size.x;
-        } else {
-            step.x = 1.0;
-            ray_length_1d.x = (map_check.x + 1.0 - x) * ray_unit_step_size.x;
-        }
-
-        if ray_dir.y < 0.0 {
-            step.y = -1.0;
-            ray_length_1d.y = (y - map_check.y) * ray_unit_step_size.y;
-        } else {
-            step.y = 1.0;
-            ray_length_1d.y = (map_check.y + 1.0 - y) * ray_unit_step_size.y;
-        }
-
-        let max_distance = 100.0;
-        let mut distance = 0.0;
-        let mut x_move;
-        while distance < max_distance {
-            if ray_length_1d.x < ray_length_1d.y {
-                map_check.x += step.x;
-                distance = ray_length_1d.x;
-                ray_length_1d.x += ray_unit_step_size.x;
-                x_move = true;
-            } else {
-                map_check.y += step.y;
-                distance = ray_length_1d.y;
-                ray_length_1d.y += ray_unit_step_size.y;
-                x_move = false;
+        let direction = Direction::new(ray_dir.x, ray_dir.y);
+        // the same rays the server traces shots along
+        let hit = maze_wars_protocol::cast_ray(maze, self.pos, direction, MAX_RAY_DISTANCE);
+        let ray_hit = hit.map(|hit| {
+            let pos = mq::Vec2::new(self.pos.x, self.pos.y) + ray_dir * hit.distance;
+
+            let map_pos = pos / TILE_SIZE;
+            let wall_pos = map_pos - map_pos.floor();
+            let wall_coord = if hit.x_side { wall_pos.y } else { wall_pos.x };
+
+            RayHit {
+                pos: Position::new(pos.x, pos.y),
+                world_distance: hit.distance,
+                x_move: hit.x_side,
+                wall_coord,
+                wall_type: maze.tiles[hit.tile.1 * maze.width + hit.tile.0],
             }
-
-            if map_check.x >= 0.0
-                && map_check.x < MAZE_WIDTH as f32
-                && map_check.y >= 0.0
-                && map_check.y < MAZE_HEIGHT as f32
-            {
-                let map_index = (map_check.y * MAZE_WIDTH as f32 + map_check.x) as usize;
-                let wall_type = maze[map_index];
-                if wall_type != 0 {
-                    let pos =
-                        mq::Vec2::new(self.pos.x, self.pos.y) + (ray_dir * distance * TILE_SIZE);
-
-                    let map_pos = pos / TILE_SIZE;
-                    let wall_pos = map_pos - map_pos.floor();
-                    let wall_coord = if x_move { wall_pos.y } else { wall_pos.x };
-
-                    return (
-                        *self,
-                        Some(RayHit {
-                            pos: Position::new(pos.x, pos.y),
-                            world_distance: distance * TILE_SIZE,
-                            x_move,
-                            wall_coord,
-                            wall_type,
-                        }),
-                    );
-                }
-            }
-        }
-
-        (*self, None)
+        });
+        (*self, ray_hit)
     }
 }
 
@@ -362,70 +328,154 @@ async fn start_game(game_session_info: GameSessionInfo) {
     });
     let player_name = game_session_info.player_name.clone();
     let player_name_copy = game_session_info.player_name.clone();
+    let server_address = game_session_info.server_address.clone();
 
-    // Wrap the socket in Arc<Mutex<>> for sharing across threads
-    let shared_socket = Arc::new(Mutex::new(socket));
     // let (tx, rx): (Sender<Vec<u8>>, Receiver<Vec<u8>>) = mpsc::channel();
     let (tx, rx): (Sender<GameState>, Receiver<GameState>) = mpsc::channel();
-    let (tx_id, rx_id): (Sender<String>, Receiver<String>) = mpsc::channel();
-    let (tx_update, rx_update): (Sender<PlayerUpdate>, Receiver<PlayerUpdate>) = mpsc::channel();
+    // our id, the server's tick rate and move speed
+    let (tx_id, rx_id): (Sender<(PlayerId, u32, f32)>, Receiver<_>) = mpsc::channel();
+    let (tx_update, rx_update): (Sender<ClientMessage>, Receiver<ClientMessage>) = mpsc::channel();
 
-    let socket_clone = shared_socket.clone();
-
-    thread::spawn(move || {
+    let communication_thread = thread::spawn(move || {
         runtime.block_on(async {
-            let socket = socket_clone.lock().unwrap();
+            // Send an initial message to the server to indicate a new connection,
+            // resuming our old player if we played on this server recently
+            let join = JoinRequest::new(
+                player_name.trim().to_string(),
+                load_session(&server_address),
+            );
+            socket
+                .send(&encode(&ClientMessage::Join(join.clone())).unwrap())
+                .await
+                .unwrap();
 
-            //format a string "new_connection:{player_name}"
-            let initial_msg = format!("new_connection:{}", player_name.clone().trim());
+            // Receive the player ID from the server
+            let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+            let (mut player_id, session, tick_rate, move_speed) = loop {
+                let len = socket.recv(&mut buf).await.unwrap();
+                match decode::<ServerMessage>(&buf[..len]) {
+                    Ok(ServerMessage::Join(JoinResponse::Accepted {
+                        id,
+                        session,
+                        tick_rate,
+                        move_speed,
+                    })) => break (id, session, tick_rate, move_speed),
+                    Ok(ServerMessage::Join(JoinResponse::Rejected { reason })) => {
+                        eprintln!("Server rejected connection: {}", reason);
+                        std::process::exit(1);
+                    }
+                    // a state that overtook the reply, it is sent again until acked
+                    Ok(_) => continue,
+                    Err(e) => {
+                        eprintln!("Invalid response from server: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            };
+            save_session(&server_address, session);
+            tx_id.send((player_id, tick_rate, move_speed)).unwrap();
+
+            // joining again after the server lost track of our address
+            let mut rejoin = JoinRequest {
+                session: Some(session),
+                ..join
+            };
 
-            // Send an initial message to the server to indicate a new connection
-            // let initial_msg = "new_connection";
-            socket.send(initial_msg.as_bytes()).await.unwrap();
+            // states we received, so deltas can be applied on top of them
+            let mut history = SnapshotHistory::default();
 
-            // Receive the player ID from the server
-            let mut buf = [0; 1024];
-            let len = socket.recv(&mut buf).await.unwrap();
-            let player_id = String::from_utf8_lossy(&buf[..len]).to_string();
-            tx_id.send(player_id.clone()).unwrap();
+            let mut last_sent = Instant::now();
 
             // COMMUNICATION LOOP
             loop {
-                let mut game_loop_update = true;
-                // Check for updates from the main game loop to send to the server
-                let player_update = match rx_update.try_recv() {
-                    Ok(update) => update,
-                    Err(_) => {
-                        game_loop_update = false;
-                        PlayerUpdate {
-                            id: player_id.clone().parse().unwrap(),
-                            action: "ping".to_string(),
-                        }
+                // Check for updates from the main game loop to send to the server,
+                // pinging when there is nothing to say so the server knows we're alive
+                let message = match rx_update.try_recv() {
+                    Ok(message) => Some(message),
+                    Err(_) if last_sent.elapsed() >= PING_INTERVAL => {
+                        Some(ClientMessage::Update(PlayerUpdate {
+                            id: player_id,
+                            action: Action::Ping,
+                        }))
                     }
+                    Err(_) => None,
                 };
 
-                if game_loop_update {
-                    let update_msg = serde_json::to_string(&player_update).unwrap();
-                    socket.send(update_msg.as_bytes()).await.unwrap();
+                if let Some(mut message) = message {
+                    // sent by the game loop before it heard we rejoined as someone new
+                    if let ClientMessage::Update(update) = &mut message {
+                        update.id = player_id;
+                    }
+                    socket.send(&encode(&message).unwrap()).await.unwrap();
+                    last_sent = Instant::now();
+                    if message == ClientMessage::Disconnect {
+                        break;
+                    }
                 }
 
-                const BUFFER_SIZE: usize = 10240;
                 // check if there is an update from the server
-                let mut buf = [0; BUFFER_SIZE];
                 if let Ok(len) = socket.try_recv(&mut buf) {
-                    let update: GameState = serde_json::from_slice(&buf[..len]).unwrap();
-                    tx.send(update).unwrap(); // If tx expects GameState
+                    let update = match decode::<ServerMessage>(&buf[..len]) {
+                        Ok(ServerMessage::Snapshot { sequence, state }) => Some((sequence, state)),
+                        Ok(ServerMessage::Delta(delta)) => history.get(delta.base).map(|base| {
+                            let mut state = base.clone();
+                            delta.apply_to(&mut state);
+                            (delta.sequence, state)
+                        }),
+                        Ok(ServerMessage::Rejoin) => {
+                            let rejoin = encode(&ClientMessage::Join(rejoin.clone())).unwrap();
+                            socket.send(&rejoin).await.unwrap();
+                            None
+                        }
+                        // a server that restarted or forgot our session takes us back
+                        // as a new player, whose states start over
+                        Ok(ServerMessage::Join(JoinResponse::Accepted {
+                            id,
+                            session,
+                            tick_rate,
+                            move_speed,
+                        })) => {
+                            if id != player_id || rejoin.session != Some(session) {
+                                player_id = id;
+                                rejoin.session = Some(session);
+                                save_session(&server_address, session);
+                                history = SnapshotHistory::default();
+                                tx_id.send((id, tick_rate, move_speed)).unwrap();
+                            }
+                            None
+                        }
+                        Ok(ServerMessage::Join(JoinResponse::Rejected { reason })) => {
+                            eprintln!("Server rejected reconnection: {}", reason);
+                            std::process::exit(1);
+                        }
+                        Err(e) => {
+                            eprintln!("Invalid message from server: {}", e);
+                            None
+                        }
+                    };
+                    // Drop states older than the one already on screen
+                    if let Some((sequence, state)) = update {
+                        if history
+                            .latest_sequence()
+                            .is_none_or(|latest| sequence > latest)
+                        {
+                            history.push(sequence, state.clone());
+                            let ack = encode(&ClientMessage::Ack { sequence }).unwrap();
+                            socket.send(&ack).await.unwrap();
+                            tx.send(state).unwrap();
+                        }
+                    }
                 }
             }
         });
     });
 
-    let player_id = rx_id.recv().unwrap();
-    let player_id: u8 = player_id.parse().unwrap();
+    let (mut player_id, tick_rate, move_speed) = rx_id.recv().unwrap();
     let wall_image = mq::Image::from_file_with_format(
         include_bytes!("../resources/WolfensteinTextures.png"),
         Some(mq::ImageFormat::Png),
     );
+    let player_frames = player_frames();
     let mut num_rays = 0.0;
     let mut output_image =
         mq::Image::gen_image_color(WINDOW_WIDTH as u16 / 2, WINDOW_HEIGHT as u16, NORD_COLOR);
@@ -433,52 +483,129 @@ async fn start_game(game_session_info: GameSessionInfo) {
 
     let player_update = PlayerUpdate {
         id: player_id,
-        action: "ping".to_string(),
+        action: Action::Ping,
     };
-    tx_update.send(player_update).unwrap();
+    tx_update
+        .send(ClientMessage::Update(player_update))
+        .unwrap();
 
     let mut game_state = rx.recv().unwrap();
 
+    let own_player = game_state.players.iter().find(|p| p.id == player_id);
+    let own_player = own_player.unwrap();
+    let mut look = Look::new(own_player, game_session_info.mouse_sensitivity);
+    let mut prediction = Prediction::new(player_id, tick_rate, move_speed, own_player.pos);
+    let mut interpolation = Interpolation::new(tick_rate);
+    interpolation.push(&game_state);
+    let mut show_debug_overlay = false;
+    look.grab_pointer(true);
+
     let target_fps = 69;
     let target_frame_duration = Duration::from_micros(1_000_000 / target_fps as u64);
     let mut last_frame_time = Instant::now();
 
-    loop {
+    // Closing the window tells the server we left instead of letting it time us out
+    mq::prevent_quit();
+
+    'frames: loop {
+        if mq::is_quit_requested() {
+            tx_update.send(ClientMessage::Disconnect).unwrap();
+            communication_thread.join().unwrap();
+            break;
+        }
+
+        // We rejoined as a new player, perhaps on a restarted server
+        if let Ok((id, tick_rate, move_speed)) = rx_id.try_recv() {
+            player_id = id;
+            prediction = Prediction::new(id, tick_rate, move_speed, prediction.pos);
+            interpolation = Interpolation::new(tick_rate);
+        }
+
+        // Escape lets go of the mouse, clicking in the window takes it again
+        if mq::is_key_pressed(mq::KeyCode::Escape) {
+            look.grab_pointer(false);
+        } else if mq::is_mouse_button_pressed(mq::MouseButton::Left) && !look.is_pointer_grabbed() {
+            look.grab_pointer(true);
+        }
+        let delta = mq::get_frame_time();
+        if look.update(delta) {
+            let player_update = PlayerUpdate {
+                id: player_id,
+                action: Action::Look {
+                    angle: look.angle,
+                    vertical: look.vertical,
+                },
+            };
+            tx_update
+                .send(ClientMessage::Update(player_update))
+                .unwrap();
+        }
+
+        // Walk our player straight away, the server makes the same moves later
+        for action in prediction.update(delta, held_move_keys(), look.angle, &game_state) {
+            let player_update = PlayerUpdate {
+                id: player_id,
+                action,
+            };
+            tx_update
+                .send(ClientMessage::Update(player_update))
+                .unwrap();
+        }
+
         // Listen for key presses and send the action to the communication thread
-        listen_for_key_presses(tx_update.clone(), player_id);
-        // Try to receive a game state update from the communication thread
-        match rx.try_recv() {
-            Ok(gs) => {
-                game_state = gs;
-            }
-            Err(mpsc::TryRecvError::Empty) => {}
-            Err(e) => {
-                eprintln!("Error receiving game state: {:?}", e);
-                break;
+        listen_for_key_presses(tx_update.clone(), player_id, look.angle);
+        // Take every game state the communication thread received since last frame
+        loop {
+            match rx.try_recv() {
+                Ok(gs) => {
+                    game_state = gs;
+                    prediction.reconcile(&game_state);
+                    interpolation.push(&game_state);
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(e) => {
+                    eprintln!("Error receiving game state: {:?}", e);
+                    break 'frames;
+                }
             }
         }
-
-        //match player id to the correct player
-        let player = game_state
-            .players
-            .iter()
-            .find(|p| p.id == player_id)
-            .unwrap()
-            .clone();
+        interpolation.advance(delta);
+        if mq::is_key_pressed(mq::KeyCode::F3) {
+            show_debug_overlay = !show_debug_overlay;
+        }
+        // everyone else a moment in the past, smoothly between the states
+        let mut players = game_state.players.clone();
+        interpolation.apply(&mut players, player_id);
+
+        //match player id to the correct player, where we walked and turned to
+        let Some(player) = game_state.players.iter().find(|p| p.id == player_id) else {
+            // not in a state yet since we rejoined
+            mq::next_frame().await;
+            continue;
+        };
+        let mut player = player.clone();
+        player.pos = prediction.pos;
+        player.angle = look.angle;
+        player.angle_vertical = look.vertical;
         let scaling_info = ScalingInfo::new();
         let floor_level =
             (WINDOW_HEIGHT as f32 / 2.0) * (1.0 + player.angle_vertical.tan() / (FOV / 2.0).tan());
-        let delta = mq::get_frame_time();
         mq::clear_background(NORD_COLOR);
-        draw_map(&game_state.maze, &scaling_info);
-        player.draw(&scaling_info);
+        let map_scale = map_scale(&game_state.maze, &scaling_info);
+        draw_map(&game_state.maze, &scaling_info, map_scale);
+        for other in players.iter().filter(|p| p.id != player_id) {
+            other.draw(&scaling_info, map_scale, mq::RED);
+        }
+        player.draw(&scaling_info, map_scale, mq::YELLOW);
 
         if num_rays < NUM_RAYS as f32 {
             num_rays += delta * RAYS_PER_SECOND;
         } else {
             num_rays = NUM_RAYS as f32;
         }
-        let ray_touches = player.cast_rays(&mut game_state.maze, num_rays as u32);
+        let ray_touches = player.cast_rays(&game_state.maze, num_rays as u32);
+        // distance to the wall in each column, so players behind walls stay hidden
+        let mut z_buffer = vec![f32::INFINITY; ray_touches.len()];
 
         for (i, (ray, ray_hit)) in ray_touches.iter().enumerate() {
             let x = i as i32;
@@ -486,6 +613,7 @@ async fn start_game(game_session_info: GameSessionInfo) {
             if let Some(ray_hit) = ray_hit {
                 let angle_between = player.angle - ray.angle;
                 let z = ray_hit.world_distance * angle_between.cos();
+                z_buffer[i] = z;
 
                 let projection_dist = (TILE_SIZE / 2.0) / (FOV / 2.0).tan();
 
@@ -529,6 +657,15 @@ async fn start_game(game_session_info: GameSessionInfo) {
             }
         }
 
+        let name_tags = draw_player_sprites(
+            &player,
+            &players,
+            &player_frames,
+            &z_buffer,
+            floor_level,
+            &mut output_image,
+        );
+
         output_texture.update(&output_image);
 
         mq::draw_texture_ex(
@@ -545,6 +682,13 @@ async fn start_game(game_session_info: GameSessionInfo) {
             },
         );
 
+        draw_name_tags(
+            &name_tags,
+            output_image.width() as f32,
+            output_image.height() as f32,
+            &scaling_info,
+        );
+
         // cross-hair
         mq::draw_line(
             scaling_info.offset.x + scaling_info.width * (3.0 / 4.0) - 10.0,
@@ -568,7 +712,7 @@ async fn start_game(game_session_info: GameSessionInfo) {
             scaling_info.offset.x + 1.0,
             scaling_info.offset.y + 1.0,
             140.0,
-            50.0,
+            65.0,
             mq::Color::new(1.0, 1.0, 1.0, 0.5),
         );
 
@@ -594,17 +738,32 @@ async fn start_game(game_session_info: GameSessionInfo) {
             20.,
             mq::BLUE,
         );
+        mq::draw_text(
+            format!(
+                "K/D: {}/{} Streak: {}",
+                player.kills, player.deaths, player.streak
+            )
+            .as_str(),
+            scaling_info.offset.x + 5.,
+            scaling_info.offset.y + 60.,
+            20.,
+            mq::BLUE,
+        );
 
-        if game_state.new_round_state {
+        if let RoundPhase::Intermission {
+            winner,
+            seconds_left,
+        } = &game_state.phase
+        {
             mq::draw_text(
-                format!("WINNER IS {}", game_state.winner).as_str(),
+                format!("WINNER IS {}", winner).as_str(),
                 scaling_info.offset.x + 300.,
                 scaling_info.offset.y + 250.,
                 50.,
                 mq::BLUE,
             );
             mq::draw_text(
-                "VI BÖRJÄR NYA ROUND MOTHERFUCKERS",
+                format!("NEXT ROUND IN {}", seconds_left).as_str(),
                 scaling_info.offset.x + 300.,
                 scaling_info.offset.y + 300.,
                 50.,
@@ -612,6 +771,67 @@ async fn start_game(game_session_info: GameSessionInfo) {
             );
         }
 
+        // who left, in the bottom left corner, newest last
+        let departures = game_state
+            .events
+            .iter()
+            .rev()
+            .filter_map(|event| match &event.kind {
+                EventKind::PlayerLeft {
+                    name,
+                    reason: LeaveReason::Quit,
+                    ..
+                } => Some(format!("{} left the game", name)),
+                EventKind::PlayerLeft {
+                    name,
+                    reason: LeaveReason::TimedOut,
+                    ..
+                } => Some(format!("{} timed out", name)),
+                EventKind::Kill { .. } => None,
+            });
+        for (i, text) in departures.enumerate() {
+            mq::draw_text(
+                text.as_str(),
+                scaling_info.offset.x + 5.,
+                scaling_info.offset.y + scaling_info.height - 10. - 20. * i as f32,
+                20.,
+                mq::YELLOW,
+            );
+        }
+
+        draw_kill_feed(&game_state.events, player_id, &scaling_info);
+
+        if show_debug_overlay {
+            draw_debug_overlay(&interpolation, &scaling_info);
+        }
+
+        if mq::is_key_down(mq::KeyCode::Tab) {
+            draw_scoreboard(&game_state.players, player_id, &scaling_info);
+        }
+
+        // tell the player who got them while the kill is still in the feed
+        let shot_by = game_state
+            .events
+            .iter()
+            .rev()
+            .find_map(|event| match &event.kind {
+                EventKind::Kill {
+                    victim,
+                    killer_name,
+                    ..
+                } if *victim == player_id => Some(killer_name),
+                _ => None,
+            });
+        if let Some(killer_name) = shot_by {
+            mq::draw_text(
+                format!("SHOT BY {}", killer_name).as_str(),
+                scaling_info.offset.x + scaling_info.width * (3.0 / 4.0) - 100.,
+                scaling_info.offset.y + scaling_info.height / 2.0 - 40.,
+                30.,
+                mq::RED,
+            );
+        }
+
         // Calculate elapsed time since the last frame
         let elapsed_time = last_frame_time.elapsed();
 
@@ -625,56 +845,30 @@ async fn start_game(game_session_info: GameSessionInfo) {
     }
 }
 
-// helper function for listening to key presses WASD left and right arrow keys and space
-// if a key is pressed send the action to the server
-fn listen_for_key_presses(tx_update: Sender<PlayerUpdate>, player_id: u8) {
-    if mq::is_key_pressed(mq::KeyCode::W) {
-        let player_update = PlayerUpdate {
-            id: player_id,
-            action: "W".to_string(),
-        };
-        tx_update.send(player_update).unwrap();
+// the WASD keys held down right now
+fn held_move_keys() -> MoveKeys {
+    MoveKeys {
+        forward: mq::is_key_down(mq::KeyCode::W),
+        backward: mq::is_key_down(mq::KeyCode::S),
+        left: mq::is_key_down(mq::KeyCode::A),
+        right: mq::is_key_down(mq::KeyCode::D),
     }
-    if mq::is_key_pressed(mq::KeyCode::A) {
-        let player_update = PlayerUpdate {
-            id: player_id,
-            action: "A".to_string(),
-        };
-        tx_update.send(player_update).unwrap();
-    }
-    if mq::is_key_pressed(mq::KeyCode::S) {
-        let player_update = PlayerUpdate {
-            id: player_id,
-            action: "S".to_string(),
-        };
-        tx_update.send(player_update).unwrap();
-    }
-    if mq::is_key_pressed(mq::KeyCode::D) {
-        let player_update = PlayerUpdate {
-            id: player_id,
-            action: "D".to_string(),
-        };
-        tx_update.send(player_update).unwrap();
-    }
-    if mq::is_key_pressed(mq::KeyCode::Left) {
-        let player_update = PlayerUpdate {
-            id: player_id,
-            action: "left".to_string(),
-        };
-        tx_update.send(player_update).unwrap();
-    }
-    if mq::is_key_pressed(mq::KeyCode::Right) {
-        let player_update = PlayerUpdate {
-            id: player_id,
-            action: "right".to_string(),
-        };
-        tx_update.send(player_update).unwrap();
-    }
-    if mq::is_key_pressed(mq::KeyCode::Space) {
-        let player_update = PlayerUpdate {
-            id: player_id,
-            action: "shoot".to_string(),
-        };
-        tx_update.send(player_update).unwrap();
+}
+
+// helper function for listening to key presses space
+// if a key is pressed send the action to the server, aimed where we face
+fn listen_for_key_presses(tx_update: Sender<ClientMessage>, player_id: PlayerId, angle: f32) {
+    let bindings = [(mq::KeyCode::Space, Action::Shoot { angle })];
+
+    for (key, action) in bindings {
+        if mq::is_key_pressed(key) {
+            let player_update = PlayerUpdate {
+                id: player_id,
+                action,
+            };
+            tx_update
+                .send(ClientMessage::Update(player_update))
+                .unwrap();
+        }
     }
 }
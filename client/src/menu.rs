@@ -2,6 +2,7 @@ use crate::shared::{AppState, AppStateData, GameSessionInfo, Server};
 use local_ip_address::local_ip;
 use macroquad::prelude::*;
 use macroquad::ui::{hash, root_ui, widgets, Skin};
+use maze_wars_protocol::MAX_NAME_LENGTH;
 use std::process::Command;
 use uuid::Uuid;
 
@@ -27,6 +28,7 @@ pub async fn show_menu() -> Option<GameSessionInfo> {
         selected_server: None,
         player_name: String::new(),
         input_ip: String::new(),
+        mouse_sensitivity: 1.0,
     };
     let skin = {
         let label_style = root_ui()
@@ -157,8 +159,9 @@ pub async fn show_menu() -> Option<GameSessionInfo> {
                     if ui.button(None, "Confirm") {
                         let trimmed_name = player_name.trim();
                         if !trimmed_name.is_empty() {
-                            // Update the player_name in AppStateData
-                            app_state.player_name = trimmed_name.to_string();
+                            // Update the player_name in AppStateData, as much of it as the server keeps
+                            app_state.player_name =
+                                trimmed_name.chars().take(MAX_NAME_LENGTH).collect();
                             current_state = AppState::MainMenu;
                         }
                     }
@@ -256,14 +259,23 @@ pub async fn show_menu() -> Option<GameSessionInfo> {
                 root_ui().window(
                     hash!(),
                     vec2(screen_center.x - 300.0, screen_center.y - 200.0),
-                    vec2(600.0, 200.0),
+                    vec2(600.0, 320.0),
                     |ui| {
                         ui.label(None, "Game Controls:");
-                        ui.label(None, "- Use WASD keys to move.");
+                        ui.label(None, "- Hold WASD keys to move.");
                         ui.label(None, "- Press 'Space' to shoot.");
-                        ui.label(None, "- use ARROW keys to look around.");
-                        ui.label(None, "First to 5 points wins the round.");
-                        ui.label(None, "Next round starts in 5 seconds.");
+                        ui.label(None, "- Move the mouse or use ARROW keys to look around.");
+                        ui.label(None, "- 'Escape' frees the mouse, click to take it back.");
+                        ui.label(None, "- Hold 'Tab' to see the scoreboard.");
+                        ui.label(None, "- Press 'F3' for network info.");
+                        ui.label(None, "First to the server's score limit wins the round.");
+                        ui.label(None, "The next round starts after a short break.");
+                        ui.slider(
+                            hash!(),
+                            "Mouse sensitivity",
+                            0.1..5.0,
+                            &mut app_state.mouse_sensitivity,
+                        );
                     },
                 );
                 render_back_button(&mut root_ui(), &mut current_state, AppState::MainMenu);
@@ -290,6 +302,7 @@ impl AppStateData {
                 .as_ref()
                 .map(|s| s.name.clone())
                 .unwrap_or_else(|| "".into()),
+            mouse_sensitivity: self.mouse_sensitivity,
         }
     }
 }
@@ -0,0 +1,257 @@
+use std::collections::VecDeque;
+
+use maze_wars_protocol::{
+    walk, walks_into, Action, GameState, MoveKeys, PlayerId, Position, RoundPhase, TILE_SIZE,
+};
+
+/// Moves kept waiting for the server at most, a few seconds' worth; a
+/// server that takes longer than that has lost them.
+const MAX_PENDING_MOVES: usize = 128;
+
+/// Ticks simulated in one frame at most, so a stalled frame doesn't flood
+/// the server with moves.
+const MAX_TICKS_PER_FRAME: u32 = 5;
+
+/// A move sent to the server that its states don't include yet.
+struct PendingMove {
+    sequence: u32,
+    keys: MoveKeys,
+    /// Where the player faced when making it.
+    angle: f32,
+}
+
+/// Our own player's position, moved as soon as a key is pressed instead of
+/// a round trip later. Moves are made at the server's tick rate, numbered,
+/// and kept until a state from the server shows it applied them; every new
+/// state is taken as the truth and the moves it doesn't include yet are
+/// made again on top of it.
+pub struct Prediction {
+    own_id: PlayerId,
+    /// Seconds a tick lasts on the server.
+    tick_length: f32,
+    /// World units a player walks in one tick.
+    walk_distance: f32,
+    /// Time not simulated yet, in seconds.
+    unsimulated: f32,
+    next_sequence: u32,
+    pending: VecDeque<PendingMove>,
+    pub pos: Position,
+}
+
+impl Prediction {
+    pub fn new(own_id: PlayerId, tick_rate: u32, move_speed: f32, pos: Position) -> Self {
+        Self {
+            own_id,
+            tick_length: 1.0 / tick_rate as f32,
+            walk_distance: move_speed * TILE_SIZE / tick_rate as f32,
+            unsimulated: 0.0,
+            next_sequence: 1,
+            pending: VecDeque::new(),
+            pos,
+        }
+    }
+
+    /// Makes the moves of the ticks `delta` seconds cover, holding `keys`
+    /// while facing `angle`. Returns them to be sent to the server.
+    pub fn update(
+        &mut self,
+        delta: f32,
+        keys: MoveKeys,
+        angle: f32,
+        state: &GameState,
+    ) -> Vec<Action> {
+        self.unsimulated =
+            (self.unsimulated + delta).min(self.tick_length * MAX_TICKS_PER_FRAME as f32);
+
+        let mut moves = Vec::new();
+        while self.unsimulated >= self.tick_length {
+            self.unsimulated -= self.tick_length;
+            let sequence = self.next_sequence;
+            self.next_sequence += 1;
+
+            let pending = PendingMove {
+                sequence,
+                keys,
+                angle,
+            };
+            self.pos = self.step(self.pos, &pending, state);
+            if self.pending.len() == MAX_PENDING_MOVES {
+                self.pending.pop_front();
+            }
+            self.pending.push_back(pending);
+            moves.push(Action::Move {
+                sequence,
+                keys,
+                angle,
+            });
+        }
+        moves
+    }
+
+    /// Starts over from where `state` has our player, then makes the moves
+    /// it doesn't include yet again.
+    pub fn reconcile(&mut self, state: &GameState) {
+        let Some(player) = state.players.iter().find(|p| p.id == self.own_id) else {
+            return;
+        };
+        while self
+            .pending
+            .front()
+            .is_some_and(|pending| pending.sequence <= player.input_sequence)
+        {
+            self.pending.pop_front();
+        }
+
+        let mut pos = player.pos;
+        for pending in &self.pending {
+            pos = self.step(pos, pending, state);
+        }
+        self.pos = pos;
+    }
+
+    /// Where one move takes us from `pos`, the way the server will make it.
+    fn step(&self, pos: Position, pending: &PendingMove, state: &GameState) -> Position {
+        // the server only moves players while a round is on
+        if state.phase != RoundPhase::Playing {
+            return pos;
+        }
+        let others: Vec<Position> = state
+            .players
+            .iter()
+            .filter(|p| p.id != self.own_id)
+            .map(|p| p.pos)
+            .collect();
+        walk(
+            pos,
+            pending.keys,
+            pending.angle,
+            self.walk_distance,
+            &state.maze,
+            |from, to| others.iter().any(|&other| walks_into(from, to, other)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maze_wars_protocol::testing::{at, maze, state};
+    use maze_wars_protocol::Player;
+
+    /// A tick lasts a quarter of a second, and a move walks one tile.
+    const TICK_RATE: u32 = 4;
+    const MOVE_SPEED: f32 = 4.0;
+
+    const FORWARD: MoveKeys = MoveKeys {
+        forward: true,
+        backward: false,
+        left: false,
+        right: false,
+    };
+
+    /// Us alone in a corridor, where the server has us at `x` with the
+    /// moves up to `input_sequence` made.
+    fn corridor(x: f32, input_sequence: u32) -> GameState {
+        let mut player = Player::new(at(x, 1.5), 0, String::from("us"));
+        player.input_sequence = input_sequence;
+        state(
+            maze(&["##########", "#........#", "##########"]),
+            0,
+            vec![player],
+        )
+    }
+
+    fn sequences(moves: &[Action]) -> Vec<u32> {
+        moves
+            .iter()
+            .map(|action| match action {
+                Action::Move { sequence, .. } => *sequence,
+                other => panic!("{:?} is not a move", other),
+            })
+            .collect()
+    }
+
+    fn assert_at(pos: Position, x: f32) {
+        let expected = at(x, 1.5);
+        assert!(
+            (pos.x - expected.x).abs() < 1e-3 && (pos.y - expected.y).abs() < 1e-3,
+            "at {:?}, expected {:?}",
+            pos,
+            expected
+        );
+    }
+
+    #[test]
+    fn every_tick_of_frame_time_is_a_numbered_move() {
+        let state = corridor(1.5, 0);
+        let mut prediction = Prediction::new(0, TICK_RATE, MOVE_SPEED, at(1.5, 1.5));
+        let moves = prediction.update(0.5, FORWARD, 0.0, &state);
+        assert_eq!(sequences(&moves), [1, 2]);
+        assert_at(prediction.pos, 3.5);
+        // time left over from a frame counts towards the next
+        assert!(prediction.update(0.125, FORWARD, 0.0, &state).is_empty());
+        let moves = prediction.update(0.125, FORWARD, 0.0, &state);
+        assert_eq!(sequences(&moves), [3]);
+        assert_eq!(
+            moves[0],
+            Action::Move {
+                sequence: 3,
+                keys: FORWARD,
+                angle: 0.0
+            }
+        );
+        assert_at(prediction.pos, 4.5);
+    }
+
+    #[test]
+    fn a_long_frame_makes_only_a_few_moves() {
+        let state = corridor(1.5, 0);
+        let mut prediction = Prediction::new(0, TICK_RATE, MOVE_SPEED, at(1.5, 1.5));
+        let moves = prediction.update(10.0, FORWARD, 0.0, &state);
+        assert_eq!(moves.len(), MAX_TICKS_PER_FRAME as usize);
+        assert_at(prediction.pos, 1.5 + MAX_TICKS_PER_FRAME as f32);
+        // and the rest of it is forgotten
+        assert!(prediction.update(0.0, FORWARD, 0.0, &state).is_empty());
+    }
+
+    #[test]
+    fn moves_the_server_made_are_dropped_and_the_rest_replayed() {
+        let mut prediction = Prediction::new(0, TICK_RATE, MOVE_SPEED, at(1.5, 1.5));
+        prediction.update(0.75, FORWARD, 0.0, &corridor(1.5, 0));
+        assert_at(prediction.pos, 4.5);
+
+        // the server made move 1 from somewhere else, 2 and 3 go on from there
+        prediction.reconcile(&corridor(2.0, 1));
+        assert_at(prediction.pos, 4.0);
+        assert_eq!(prediction.pending.len(), 2);
+
+        prediction.reconcile(&corridor(3.0, 3));
+        assert_at(prediction.pos, 3.0);
+        assert!(prediction.pending.is_empty());
+    }
+
+    #[test]
+    fn a_state_without_us_changes_nothing() {
+        let mut prediction = Prediction::new(7, TICK_RATE, MOVE_SPEED, at(1.5, 1.5));
+        prediction.update(0.25, FORWARD, 0.0, &corridor(5.5, 0));
+        prediction.reconcile(&corridor(5.5, 1));
+        assert_at(prediction.pos, 2.5);
+        assert_eq!(prediction.pending.len(), 1);
+    }
+
+    #[test]
+    fn nobody_moves_outside_of_a_round() {
+        let mut state = corridor(1.5, 0);
+        state.phase = RoundPhase::Intermission {
+            winner: String::from("them"),
+            seconds_left: 3,
+        };
+        let mut prediction = Prediction::new(0, TICK_RATE, MOVE_SPEED, at(1.5, 1.5));
+        // the moves are still sent, the server acknowledges them
+        let moves = prediction.update(0.5, FORWARD, 0.0, &state);
+        assert_eq!(sequences(&moves), [1, 2]);
+        assert_at(prediction.pos, 1.5);
+        prediction.reconcile(&state);
+        assert_at(prediction.pos, 1.5);
+    }
+}
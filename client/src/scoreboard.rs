@@ -0,0 +1,60 @@
+use macroquad::prelude as mq;
+use maze_wars_protocol::{Player, PlayerId};
+
+use crate::ScalingInfo;
+
+const ROW_HEIGHT: f32 = 24.0;
+const FONT_SIZE: f32 = 22.0;
+
+/// Column titles and where each column starts, as a share of the board's
+/// width.
+const COLUMNS: [(&str, f32); 5] = [
+    ("Name", 0.04),
+    ("Score", 0.46),
+    ("Kills", 0.60),
+    ("Deaths", 0.74),
+    ("Ping", 0.88),
+];
+
+/// Every player in the game, best score first, over the middle of the
+/// screen. Shown while Tab is held.
+pub fn draw_scoreboard(players: &[Player], own_id: PlayerId, scaling_info: &ScalingInfo) {
+    let mut players: Vec<&Player> = players.iter().collect();
+    players.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then(b.kills.cmp(&a.kills))
+            .then(a.deaths.cmp(&b.deaths))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    let width = scaling_info.width * 0.5;
+    let height = ROW_HEIGHT * (players.len() + 2) as f32;
+    let x = scaling_info.offset.x + (scaling_info.width - width) / 2.0;
+    let y = scaling_info.offset.y + ((scaling_info.height - height) / 2.0).max(0.0);
+    mq::draw_rectangle(x, y, width, height, mq::Color::new(0.0, 0.0, 0.0, 0.7));
+
+    let row = |index: usize, cells: [String; 5], color: mq::Color| {
+        let baseline = y + ROW_HEIGHT * (index as f32 + 1.0);
+        for (cell, (_, start)) in cells.iter().zip(COLUMNS) {
+            mq::draw_text(cell, x + width * start, baseline, FONT_SIZE, color);
+        }
+    };
+
+    row(0, COLUMNS.map(|(title, _)| title.to_string()), mq::GRAY);
+    for (index, player) in players.iter().enumerate() {
+        let color = if player.id == own_id {
+            mq::YELLOW
+        } else {
+            mq::WHITE
+        };
+        let cells = [
+            player.name.clone(),
+            player.score.to_string(),
+            player.kills.to_string(),
+            player.deaths.to_string(),
+            format!("{} ms", player.ping),
+        ];
+        row(index + 1, cells, color);
+    }
+}
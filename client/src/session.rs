@@ -0,0 +1,73 @@
+use maze_wars_protocol::SessionToken;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// One line per server: "<address> <token>". Kept in the user's own data
+// directory, the tokens are as good as the players they belong to.
+fn session_file() -> Option<PathBuf> {
+    Some(data_dir()?.join("maze-wars").join("sessions"))
+}
+
+/// Where the platform keeps applications' files for the current user.
+fn data_dir() -> Option<PathBuf> {
+    let from_env = |name| {
+        env::var_os(name)
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+    };
+    if cfg!(windows) {
+        from_env("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        Some(from_env("HOME")?.join("Library/Application Support"))
+    } else {
+        from_env("XDG_DATA_HOME").or_else(|| Some(from_env("HOME")?.join(".local/share")))
+    }
+}
+
+/// The token we were given the last time we joined `server_address`.
+pub fn load_session(server_address: &str) -> Option<SessionToken> {
+    let contents = fs::read_to_string(session_file()?).ok()?;
+    contents.lines().find_map(|line| {
+        let (address, token) = line.split_once(' ')?;
+        if address == server_address {
+            token.parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// Remember `token` so a restarted client can reclaim its player.
+pub fn save_session(server_address: &str, token: SessionToken) {
+    let Some(path) = session_file() else {
+        eprintln!("Failed to save session: no home directory");
+        return;
+    };
+    let contents = fs::read_to_string(&path).unwrap_or_default();
+    let mut lines: Vec<String> = contents
+        .lines()
+        .filter(|line| line.split_once(' ').map(|(address, _)| address) != Some(server_address))
+        .map(String::from)
+        .collect();
+    lines.push(format!("{} {}", server_address, token));
+    if let Err(e) = write_private(&path, &lines.join("\n")) {
+        eprintln!("Failed to save session: {}", e);
+    }
+}
+
+/// Writes `contents` to `path`, creating it readable by the user alone.
+fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(contents.as_bytes())
+}
@@ -13,11 +13,14 @@ pub enum AppState {
 
 #[derive(Clone)]
 pub struct AppStateData {
+    #[allow(dead_code)]
     pub current_state: AppState,
     pub servers: Vec<Server>,
     pub selected_server: Option<Server>,
     pub player_name: String,
+    #[allow(dead_code)]
     pub input_ip: String,
+    pub mouse_sensitivity: f32,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -26,6 +29,8 @@ pub struct GameSessionInfo {
     pub created_servers: Vec<Server>,
     pub joined_server: Option<Server>,
     pub server_address: String,
+    /// How fast the mouse turns the player, 1 being the default.
+    pub mouse_sensitivity: f32,
 }
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Server {
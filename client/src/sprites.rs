@@ -0,0 +1,204 @@
+use std::f32::consts::TAU;
+
+use macroquad::prelude as mq;
+use maze_wars_protocol::Player;
+
+use crate::{Lerp, ScalingInfo, BACKGROUND_COLOR, FOV, TILE_SIZE, VIEW_DISTANCE, WINDOW_HEIGHT};
+
+/// Views of a player, one for every eighth of a turn they are facing away
+/// from the viewer, starting with the one looking straight at them.
+const NUM_FRAMES: usize = 8;
+const FRAME_SIZE: u16 = 64;
+
+/// How tall a player is next to a wall.
+const PLAYER_HEIGHT: f32 = 0.8;
+
+/// Players closer than this are inside the camera and not drawn.
+const NEAR_PLANE: f32 = 1.0;
+
+const NAME_TAG_SIZE: f32 = 16.0;
+
+const SUIT_COLOR: mq::Color = mq::Color::new(0.55, 0.1, 0.1, 1.0);
+const SKIN_COLOR: mq::Color = mq::Color::new(0.9, 0.7, 0.55, 1.0);
+const HAIR_COLOR: mq::Color = mq::Color::new(0.3, 0.2, 0.1, 1.0);
+const DARK_COLOR: mq::Color = mq::Color::new(0.15, 0.15, 0.15, 1.0);
+
+/// Where a player's name goes, in pixels of the 3D view's image.
+pub struct NameTag {
+    name: String,
+    x: f32,
+    y: f32,
+}
+
+/// The frames players are drawn with. There is no artwork for players in the
+/// texture sheet, so they are painted here once at startup.
+pub fn player_frames() -> Vec<mq::Image> {
+    (0..NUM_FRAMES)
+        .map(|frame| player_frame(frame as f32 * TAU / NUM_FRAMES as f32))
+        .collect()
+}
+
+/// A player turned `turn` radians away from the viewer: 0 is their front
+/// and PI their back.
+fn player_frame(turn: f32) -> mq::Image {
+    let mut image = mq::Image::gen_image_color(FRAME_SIZE, FRAME_SIZE, mq::BLANK);
+    // how far their front points to the right of the picture, and at the viewer
+    let side = -turn.sin();
+    let front = turn.cos();
+
+    let body = 0.2 * (0.6 + 0.4 * front.abs());
+    let head = 0.5 + 0.04 * side;
+    let gun = 0.5 + 0.15 * side;
+    let gun_width = (0.15 * side.abs()).max(0.05);
+    let paint_gun = |image: &mut mq::Image| {
+        paint(image, DARK_COLOR, |x, y| {
+            (x - gun).abs() < gun_width && (0.5..0.56).contains(&y)
+        })
+    };
+
+    if front < 0.0 {
+        paint_gun(&mut image);
+    }
+    paint(&mut image, DARK_COLOR, |x, y| {
+        y > 0.72 && ((0.36..0.47).contains(&x) || (0.53..0.64).contains(&x))
+    });
+    paint(&mut image, SUIT_COLOR, |x, y| {
+        (x - 0.5).abs() < body && (0.38..0.75).contains(&y)
+    });
+    paint(&mut image, SKIN_COLOR, |x, y| {
+        (x - head).hypot(y - 0.25) < 0.13
+    });
+    paint(&mut image, HAIR_COLOR, |x, y| {
+        (x - head).hypot(y - 0.25) < 0.13 && (y < 0.19 || front < -0.2)
+    });
+    if front > -0.2 {
+        let eyes = 0.045 * front.max(0.0);
+        let eye = head + 0.06 * side;
+        paint(&mut image, DARK_COLOR, |x, y| {
+            ((x - eye).abs() - eyes).abs().hypot(y - 0.25) < 0.025
+        });
+    }
+    if front >= 0.0 {
+        paint_gun(&mut image);
+    }
+    image
+}
+
+/// Colors the pixels of `image` whose centre is inside `shape`, which takes
+/// coordinates from 0 to 1 across and down the image.
+fn paint(image: &mut mq::Image, color: mq::Color, shape: impl Fn(f32, f32) -> bool) {
+    let size = FRAME_SIZE as f32;
+    for y in 0..FRAME_SIZE as u32 {
+        for x in 0..FRAME_SIZE as u32 {
+            if shape((x as f32 + 0.5) / size, (y as f32 + 0.5) / size) {
+                image.set_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+/// Which frame shows `player` as `viewer` sees them.
+fn frame_index(viewer: &Player, player: &Player) -> usize {
+    let to_viewer = (viewer.pos.y - player.pos.y).atan2(viewer.pos.x - player.pos.x);
+    let turn = (player.angle - to_viewer).rem_euclid(TAU);
+    (turn / (TAU / NUM_FRAMES as f32)).round() as usize % NUM_FRAMES
+}
+
+/// Draws every other player into the 3D view as a sprite facing the camera,
+/// furthest first and only in the columns where they are in front of the
+/// walls. `z_buffer` holds each column's wall distance. Returns where the
+/// names of the players that can be seen go.
+pub fn draw_player_sprites(
+    viewer: &Player,
+    players: &[Player],
+    frames: &[mq::Image],
+    z_buffer: &[f32],
+    floor_level: f32,
+    output_image: &mut mq::Image,
+) -> Vec<NameTag> {
+    let columns = z_buffer.len() as f32;
+    let (sin, cos) = viewer.angle.sin_cos();
+    let projection_dist = (TILE_SIZE / 2.0) / (FOV / 2.0).tan();
+
+    let mut sprites: Vec<(f32, f32, f32, &Player)> = players
+        .iter()
+        .filter(|p| p.id != viewer.id)
+        .filter_map(|p| {
+            let dx = p.pos.x - viewer.pos.x;
+            let dy = p.pos.y - viewer.pos.y;
+            let depth = dx * cos + dy * sin;
+            let across = dy * cos - dx * sin;
+            let distance = dx.hypot(dy);
+            (depth > NEAR_PLANE && distance < VIEW_DISTANCE).then_some((depth, across, distance, p))
+        })
+        .collect();
+    sprites.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut name_tags = Vec::new();
+    for (depth, across, distance, player) in sprites {
+        let frame = &frames[frame_index(viewer, player)];
+        let fog_brightness = (2.0 * distance / VIEW_DISTANCE - 1.0).max(0.0);
+
+        let wall_height = (WINDOW_HEIGHT as f32 * projection_dist) / depth;
+        let size = wall_height * PLAYER_HEIGHT;
+        let bottom = floor_level + wall_height / 2.0;
+        let top = bottom - size;
+        let centre = columns * (across / depth / FOV + 0.5);
+        let left = centre - size / 2.0;
+
+        let x0 = left.max(0.0) as usize;
+        let x1 = (left + size).clamp(0.0, columns) as usize;
+        let y0 = top.max(0.0) as u32;
+        let y1 = bottom.clamp(0.0, output_image.height() as f32) as u32;
+
+        let mut seen = false;
+        for x in (x0..x1).filter(|&x| depth < z_buffer[x]) {
+            seen = true;
+            let texture_x = ((x as f32 - left) / size * FRAME_SIZE as f32) as u32;
+            let texture_x = texture_x.min(FRAME_SIZE as u32 - 1);
+            for y in y0..y1 {
+                let texture_y = ((y as f32 - top) / size * FRAME_SIZE as f32) as u32;
+                let color = frame.get_pixel(texture_x, texture_y.min(FRAME_SIZE as u32 - 1));
+                if color.a > 0.0 {
+                    let color_with_fog = color.lerp(BACKGROUND_COLOR, fog_brightness);
+                    output_image.set_pixel(x as u32, y, color_with_fog);
+                }
+            }
+        }
+
+        if seen {
+            name_tags.push(NameTag {
+                name: player.name.clone(),
+                x: centre,
+                y: top,
+            });
+        }
+    }
+    name_tags
+}
+
+/// Puts the names over the players' heads, once the 3D view of
+/// `image_width` by `image_height` pixels is on screen.
+pub fn draw_name_tags(
+    name_tags: &[NameTag],
+    image_width: f32,
+    image_height: f32,
+    scaling_info: &ScalingInfo,
+) {
+    let scale_x = scaling_info.width / 2.0 / image_width;
+    let scale_y = scaling_info.height / image_height;
+    for tag in name_tags {
+        let size = mq::measure_text(&tag.name, None, NAME_TAG_SIZE as u16, 1.0);
+        let x =
+            scaling_info.offset.x + scaling_info.width / 2.0 + tag.x * scale_x - size.width / 2.0;
+        let y = scaling_info.offset.y + (tag.y * scale_y - 4.0).max(size.height);
+        mq::draw_rectangle(
+            x - 2.0,
+            y - size.height - 2.0,
+            size.width + 4.0,
+            size.height + 4.0,
+            mq::Color::new(0.0, 0.0, 0.0, 0.5),
+        );
+        mq::draw_text(&tag.name, x, y, NAME_TAG_SIZE, mq::WHITE);
+    }
+}
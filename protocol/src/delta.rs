@@ -0,0 +1,223 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{GameEvent, GameState, MazeSeed, Player, PlayerId, RoundPhase};
+
+/// How many sent states either side remembers to resolve a delta base.
+pub const SNAPSHOT_HISTORY_LEN: usize = 32;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileChange {
+    pub index: u32,
+    pub tile: u8,
+}
+
+/// The difference between two game states.
+///
+/// Only players and maze tiles that changed are carried; the small round
+/// fields and the recent events are always sent.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StateDelta {
+    /// Sequence number of the state this delta applies on top of.
+    pub base: u32,
+    pub sequence: u32,
+    pub changed_players: Vec<Player>,
+    pub removed_players: Vec<PlayerId>,
+    pub changed_tiles: Vec<TileChange>,
+    pub seed: Option<MazeSeed>,
+    pub round: usize,
+    pub tick: u32,
+    pub phase: RoundPhase,
+    pub events: Vec<GameEvent>,
+}
+
+impl StateDelta {
+    /// Returns `None` when the two states can't be diffed (the maze changed
+    /// dimensions), in which case a full snapshot has to be sent instead.
+    pub fn between(
+        base_sequence: u32,
+        base: &GameState,
+        sequence: u32,
+        target: &GameState,
+    ) -> Option<Self> {
+        if !base.maze.same_size(&target.maze) {
+            return None;
+        }
+
+        let changed_players = target
+            .players
+            .iter()
+            .filter(|player| !base.players.contains(player))
+            .cloned()
+            .collect();
+        let removed_players = base
+            .players
+            .iter()
+            .filter(|old| !target.players.iter().any(|new| new.id == old.id))
+            .map(|old| old.id)
+            .collect();
+        let changed_tiles = base
+            .maze
+            .tiles
+            .iter()
+            .zip(target.maze.tiles.iter())
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(index, (_, &tile))| TileChange {
+                index: index as u32,
+                tile,
+            })
+            .collect();
+
+        Some(Self {
+            base: base_sequence,
+            sequence,
+            changed_players,
+            removed_players,
+            changed_tiles,
+            seed: target.seed,
+            round: target.round,
+            tick: target.tick,
+            phase: target.phase.clone(),
+            events: target.events.clone(),
+        })
+    }
+
+    pub fn apply_to(&self, state: &mut GameState) {
+        state
+            .players
+            .retain(|player| !self.removed_players.contains(&player.id));
+        for player in &self.changed_players {
+            match state.players.iter_mut().find(|p| p.id == player.id) {
+                Some(existing) => *existing = player.clone(),
+                None => state.players.push(player.clone()),
+            }
+        }
+        for change in &self.changed_tiles {
+            if let Some(tile) = state.maze.tiles.get_mut(change.index as usize) {
+                *tile = change.tile;
+            }
+        }
+        state.seed = self.seed;
+        state.round = self.round;
+        state.tick = self.tick;
+        state.phase = self.phase.clone();
+        state.events = self.events.clone();
+    }
+}
+
+/// The last few states sent or received, by sequence number.
+#[derive(Debug, Default)]
+pub struct SnapshotHistory {
+    snapshots: VecDeque<(u32, GameState)>,
+}
+
+impl SnapshotHistory {
+    pub fn push(&mut self, sequence: u32, state: GameState) {
+        if self.snapshots.len() == SNAPSHOT_HISTORY_LEN {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back((sequence, state));
+    }
+
+    pub fn get(&self, sequence: u32) -> Option<&GameState> {
+        self.snapshots
+            .iter()
+            .find(|(seq, _)| *seq == sequence)
+            .map(|(_, state)| state)
+    }
+
+    pub fn latest_sequence(&self) -> Option<u32> {
+        self.snapshots.back().map(|(seq, _)| *seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{sample_player, sample_state};
+    use crate::{
+        decode, encode, EventKind, LeaveReason, Maze, Position, ServerMessage, BREAKABLE, EMPTY,
+    };
+
+    #[test]
+    fn delta_reproduces_target() {
+        let base = sample_state();
+        let mut target = base.clone();
+        target.players[1].pos = Position::new(100.0, 12.0);
+        target.players.remove(0);
+        target.players.push(sample_player(5));
+        target.maze.tiles[30] = EMPTY;
+        target.maze.tiles[31] = BREAKABLE;
+        target.seed = None;
+        target.round = 3;
+        target.tick += 1;
+        target.phase = RoundPhase::Intermission {
+            winner: String::from("player1"),
+            seconds_left: 5,
+        };
+        target.events.push(GameEvent {
+            tick: target.tick,
+            kind: EventKind::PlayerLeft {
+                id: 0,
+                name: String::from("player0"),
+                reason: LeaveReason::Quit,
+            },
+        });
+
+        let delta = StateDelta::between(1, &base, 2, &target).unwrap();
+        assert_eq!(delta.changed_players.len(), 2);
+        assert_eq!(delta.removed_players, vec![0]);
+        assert_eq!(delta.changed_tiles.len(), 2);
+
+        let mut applied = base.clone();
+        delta.apply_to(&mut applied);
+        assert_eq!(applied, target);
+    }
+
+    #[test]
+    fn delta_is_smaller_than_snapshot() {
+        let base = sample_state();
+        let mut target = base.clone();
+        target.players[0].pos.x += 21.0;
+        target.maze.tiles[25] = BREAKABLE;
+        target.maze.tiles[26] = EMPTY;
+
+        let delta = StateDelta::between(1, &base, 2, &target).unwrap();
+        let delta_bytes = encode(&ServerMessage::Delta(delta.clone())).unwrap();
+        let snapshot_bytes = encode(&ServerMessage::Snapshot {
+            sequence: 2,
+            state: target,
+        })
+        .unwrap();
+        assert!(delta_bytes.len() * 10 < snapshot_bytes.len());
+        assert_eq!(
+            decode::<ServerMessage>(&delta_bytes).unwrap(),
+            ServerMessage::Delta(delta)
+        );
+    }
+
+    #[test]
+    fn resized_maze_needs_snapshot() {
+        let base = sample_state();
+        let mut target = base.clone();
+        target.maze = Maze::new(32, 18, target.maze.tiles);
+        assert!(StateDelta::between(1, &base, 2, &target).is_none());
+    }
+
+    #[test]
+    fn history_forgets_oldest() {
+        let mut history = SnapshotHistory::default();
+        for sequence in 0..SNAPSHOT_HISTORY_LEN as u32 + 3 {
+            history.push(sequence, sample_state());
+        }
+        assert!(history.get(0).is_none());
+        assert!(history.get(2).is_none());
+        assert!(history.get(3).is_some());
+        assert_eq!(
+            history.latest_sequence(),
+            Some(SNAPSHOT_HISTORY_LEN as u32 + 2)
+        );
+    }
+}
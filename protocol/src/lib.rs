@@ -0,0 +1,507 @@
+//! Wire types shared by the server and the client.
+//!
+//! Everything that crosses the socket is defined here once, so a change to a
+//! message is a compile error on both ends instead of a silent parse failure.
+//! So is the movement both ends simulate.
+
+mod delta;
+mod movement;
+mod raycast;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod wire;
+
+pub use delta::{SnapshotHistory, StateDelta, TileChange, SNAPSHOT_HISTORY_LEN};
+pub use movement::{walk, walks_into, PLAYER_RADIUS};
+pub use raycast::{cast_ray, WallHit};
+pub use wire::{decode, encode, DecodeError, EncodeError, MAX_DATAGRAM_SIZE};
+
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever a message in this crate changes shape.
+pub const PROTOCOL_VERSION: u32 = 17;
+
+/// Side of a maze tile in world units, which positions are measured in.
+pub const TILE_SIZE: f32 = 64.0 / 3.0;
+
+// Maze tiles are terrain only, players are found in `GameState::players`.
+pub const EMPTY: u8 = 0;
+pub const WALL: u8 = 2;
+pub const BREAKABLE: u8 = 3;
+
+pub type PlayerId = u32;
+
+/// Longest player name the server keeps, in characters; it cuts longer ones.
+pub const MAX_NAME_LENGTH: usize = 24;
+
+/// Most players the server lets into a game, and most events it keeps in a
+/// state. With the longest names that keeps a state for the largest maze
+/// within a datagram.
+pub const MAX_PLAYERS: usize = 64;
+pub const MAX_EVENTS: usize = 64;
+
+/// Furthest a player can look up or down, in radians from level.
+pub const MAX_LOOK_VERTICAL: f32 = std::f32::consts::FRAC_PI_6;
+
+/// Secret handed to a client on join; presenting it again reclaims the same
+/// player after a reconnect.
+pub type SessionToken = u64;
+
+/// Seed a generated maze was built from; the same seed, level and size
+/// always give the same maze.
+pub type MazeSeed = u32;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Position {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self { x: 1.0, y: 0.0 }
+    }
+}
+
+pub type Direction = Position;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Player {
+    pub id: PlayerId,
+    pub pos: Position,
+    pub direction: Direction,
+    pub angle: f32,          // in radians
+    pub angle_vertical: f32, // in radians
+    pub name: String,
+    pub score: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    /// Kills since the player last died.
+    pub streak: u32,
+    pub best_streak: u32,
+    /// Round trip time to the server in milliseconds, as the server measured it.
+    pub ping: u32,
+    /// Newest of this player's movement inputs the server has applied.
+    pub input_sequence: u32,
+}
+
+impl Player {
+    pub fn new(pos: Position, id: PlayerId, name: String) -> Self {
+        Self {
+            id,
+            pos,
+            direction: Direction::default(),
+            angle: 0.0,
+            angle_vertical: 0.0,
+            name,
+            score: 0,
+            kills: 0,
+            deaths: 0,
+            streak: 0,
+            best_streak: 0,
+            ping: 0,
+            input_sequence: 0,
+        }
+    }
+}
+
+/// First message a client sends to the server.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct JoinRequest {
+    pub version: u32,
+    pub name: String,
+    /// Token from an earlier join, to take that player back.
+    pub session: Option<SessionToken>,
+}
+
+impl JoinRequest {
+    pub fn new(name: String, session: Option<SessionToken>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            name,
+            session,
+        }
+    }
+}
+
+/// Server reply to a [`JoinRequest`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum JoinResponse {
+    /// `tick_rate` and `move_speed` are what the client needs to move its
+    /// player the way the server will.
+    Accepted {
+        id: PlayerId,
+        session: SessionToken,
+        tick_rate: u32,
+        /// Tiles a second a player walks.
+        move_speed: f32,
+    },
+    Rejected {
+        reason: String,
+    },
+}
+
+/// Movement keys a player is holding down.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MoveKeys {
+    pub forward: bool,
+    pub backward: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+/// Something a player asks the server to do.
+///
+/// New variants can carry their own fields; a variant the receiver does not
+/// know fails to deserialize instead of being ignored.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// The movement keys held for one tick, sent every tick, and the
+    /// `angle` the player faced while holding them, which the move goes by
+    /// even if a `Look` got lost. Numbered so the client can tell which of
+    /// its moves a state already includes.
+    Move {
+        sequence: u32,
+        keys: MoveKeys,
+        angle: f32,
+    },
+    /// Where the player looks now: `angle` around, `vertical` up (positive)
+    /// or down. Sent whenever it changes; the server wraps the angle and
+    /// holds `vertical` within [`MAX_LOOK_VERTICAL`].
+    Look { angle: f32, vertical: f32 },
+    /// Fires along `angle`, where the player faced when pulling the trigger.
+    Shoot { angle: f32 },
+    /// Keeps the connection alive without changing anything.
+    Ping,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PlayerUpdate {
+    pub id: PlayerId,
+    pub action: Action,
+}
+
+/// A rectangular grid of tiles, stored row by row.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Maze {
+    pub width: usize,
+    pub height: usize,
+    pub tiles: Vec<u8>,
+}
+
+impl Maze {
+    pub fn new(width: usize, height: usize, tiles: Vec<u8>) -> Self {
+        assert_eq!(tiles.len(), width * height, "tiles don't fill the maze");
+        Self {
+            width,
+            height,
+            tiles,
+        }
+    }
+
+    /// Index into `tiles` of the tile at column `x`, row `y`, or `None` when
+    /// that lies outside the maze.
+    pub fn index(&self, x: usize, y: usize) -> Option<usize> {
+        (x < self.width && y < self.height).then(|| y * self.width + x)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<u8> {
+        self.index(x, y).map(|index| self.tiles[index])
+    }
+
+    pub fn same_size(&self, other: &Maze) -> bool {
+        self.width == other.width && self.height == other.height
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GameState {
+    pub players: Vec<Player>,
+    pub maze: Maze,
+    /// What the maze was generated from; `None` for a hand-made map.
+    pub seed: Option<MazeSeed>,
+    pub round: usize,
+    /// Server tick this state was produced on.
+    pub tick: u32,
+    pub phase: RoundPhase,
+    /// Things that happened in the last few seconds. They are repeated in
+    /// every state until they expire so a lost datagram doesn't lose them.
+    pub events: Vec<GameEvent>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RoundPhase {
+    Playing,
+    /// Someone reached the score limit; the next round starts when the
+    /// countdown runs out.
+    Intermission {
+        winner: String,
+        seconds_left: u32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GameEvent {
+    /// Server tick the event happened on.
+    pub tick: u32,
+    pub kind: EventKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum EventKind {
+    PlayerLeft {
+        id: PlayerId,
+        name: String,
+        reason: LeaveReason,
+    },
+    Kill {
+        killer: PlayerId,
+        killer_name: String,
+        victim: PlayerId,
+        victim_name: String,
+        weapon: Weapon,
+    },
+}
+
+/// What a kill was made with.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weapon {
+    Blaster,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LeaveReason {
+    /// The client said goodbye.
+    Quit,
+    /// Nothing was heard from the client for too long.
+    TimedOut,
+}
+
+/// Everything a client sends. `Join` must stay the first variant so a client
+/// with an older protocol still gets a readable rejection.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ClientMessage {
+    Join(JoinRequest),
+    Update(PlayerUpdate),
+    /// The client has applied the state with this sequence number, so the
+    /// server may send deltas against it.
+    Ack {
+        sequence: u32,
+    },
+    /// The player is leaving; the server frees their slot right away instead
+    /// of waiting for a timeout.
+    Disconnect,
+}
+
+/// Everything the server sends.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ServerMessage {
+    Join(JoinResponse),
+    Snapshot {
+        sequence: u32,
+        state: GameState,
+    },
+    Delta(StateDelta),
+    /// Sent in reply to anything but a join from an address the server has
+    /// no client for, e.g. after a NAT rebinding. The client should join
+    /// again with its session token.
+    Rejoin,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+
+    fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> T {
+        decode(&encode(value).unwrap()).unwrap()
+    }
+
+    pub(crate) fn sample_player(id: PlayerId) -> Player {
+        let mut player = Player::new(Position::new(32.0, 53.5), id, format!("player{id}"));
+        player.angle = std::f32::consts::FRAC_PI_2;
+        player.score = 3;
+        player.kills = 4;
+        player.deaths = 2;
+        player.streak = 1;
+        player.best_streak = 3;
+        player.ping = 48;
+        player.input_sequence = 1234;
+        player
+    }
+
+    pub(crate) fn sample_state() -> GameState {
+        let mut tiles = vec![EMPTY; 24 * 24];
+        for (i, tile) in tiles.iter_mut().enumerate() {
+            if i % 3 == 0 {
+                *tile = WALL;
+            }
+        }
+        GameState {
+            players: vec![sample_player(0), sample_player(1)],
+            maze: Maze::new(24, 24, tiles),
+            seed: Some(0x5eed),
+            round: 2,
+            tick: 40,
+            phase: RoundPhase::Playing,
+            events: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn player_update_round_trip() {
+        let actions = [
+            Action::Move {
+                sequence: 300,
+                keys: MoveKeys {
+                    backward: true,
+                    left: true,
+                    ..MoveKeys::default()
+                },
+                angle: 0.5,
+            },
+            Action::Look {
+                angle: 1.25,
+                vertical: -0.5,
+            },
+            Action::Shoot { angle: 3.0 },
+            Action::Ping,
+        ];
+        for action in actions {
+            let update = ClientMessage::Update(PlayerUpdate { id: 7, action });
+            assert_eq!(round_trip(&update), update);
+        }
+    }
+
+    #[test]
+    fn unknown_action_is_rejected() {
+        // id 0 followed by an action variant index nobody defined
+        assert!(decode::<PlayerUpdate>(&[0, 99]).is_err());
+        // a move with a key that is neither held nor released
+        assert!(decode::<PlayerUpdate>(&[0, 0, 1, 9]).is_err());
+        assert!(decode::<ClientMessage>(&[42]).is_err());
+    }
+
+    #[test]
+    fn game_state_round_trip() {
+        let mut state = sample_state();
+        state.phase = RoundPhase::Intermission {
+            winner: String::from("player1"),
+            seconds_left: 4,
+        };
+        state.events.push(GameEvent {
+            tick: 38,
+            kind: EventKind::PlayerLeft {
+                id: 3,
+                name: String::from("player3"),
+                reason: LeaveReason::TimedOut,
+            },
+        });
+        state.events.push(GameEvent {
+            tick: 39,
+            kind: EventKind::Kill {
+                killer: 1,
+                killer_name: String::from("player1"),
+                victim: 0,
+                victim_name: String::from("player0"),
+                weapon: Weapon::Blaster,
+            },
+        });
+        let message = ServerMessage::Snapshot { sequence: 9, state };
+        assert_eq!(round_trip(&message), message);
+    }
+
+    #[test]
+    fn join_round_trip() {
+        let request = JoinRequest::new(String::from("foo"), None);
+        assert_eq!(request.version, PROTOCOL_VERSION);
+        let request = ClientMessage::Join(request);
+        assert_eq!(round_trip(&request), request);
+        let resume = ClientMessage::Join(JoinRequest::new(String::from("foo"), Some(u64::MAX)));
+        assert_eq!(round_trip(&resume), resume);
+
+        let accepted = ServerMessage::Join(JoinResponse::Accepted {
+            id: 4,
+            session: 0xdead_beef_cafe,
+            tick_rate: 30,
+            move_speed: 4.0,
+        });
+        assert_eq!(round_trip(&accepted), accepted);
+        let rejected = ServerMessage::Join(JoinResponse::Rejected {
+            reason: String::from("version mismatch"),
+        });
+        assert_eq!(round_trip(&rejected), rejected);
+        assert_eq!(round_trip(&ServerMessage::Rejoin), ServerMessage::Rejoin);
+    }
+
+    #[test]
+    fn ack_round_trip() {
+        let ack = ClientMessage::Ack { sequence: 1234 };
+        assert_eq!(round_trip(&ack), ack);
+        assert_eq!(
+            round_trip(&ClientMessage::Disconnect),
+            ClientMessage::Disconnect
+        );
+    }
+
+    #[test]
+    fn full_snapshot_fits_in_a_datagram() {
+        let mut state = sample_state();
+        state.players = (0..64).map(sample_player).collect();
+        let bytes = encode(&ServerMessage::Snapshot { sequence: 1, state }).unwrap();
+        assert!(bytes.len() < MAX_DATAGRAM_SIZE);
+        // far below the old json encoding, which spent several bytes per tile
+        assert!(bytes.len() < 24 * 24 + 64 * 64);
+    }
+
+    #[test]
+    fn fullest_state_of_the_largest_maze_fits_in_a_datagram() {
+        // the longest names, in characters that take four bytes each
+        let name = "\u{1F600}".repeat(MAX_NAME_LENGTH);
+        let mut state = sample_state();
+        state.maze = Maze::new(128, 128, vec![WALL; 128 * 128]);
+        state.players = (0..MAX_PLAYERS as PlayerId)
+            .map(|id| Player {
+                name: name.clone(),
+                ..sample_player(id)
+            })
+            .collect();
+        state.events = (0..MAX_EVENTS as u32)
+            .map(|tick| GameEvent {
+                tick,
+                kind: EventKind::Kill {
+                    killer: 1,
+                    killer_name: name.clone(),
+                    victim: 0,
+                    victim_name: name.clone(),
+                    weapon: Weapon::Blaster,
+                },
+            })
+            .collect();
+        assert!(encode(&ServerMessage::Snapshot { sequence: 1, state }).is_ok());
+    }
+
+    #[test]
+    fn oversized_message_fails_to_encode() {
+        let mut state = sample_state();
+        state.maze = Maze::new(256, 256, vec![WALL; 256 * 256]);
+        assert!(encode(&ServerMessage::Snapshot { sequence: 1, state }).is_err());
+    }
+
+    #[test]
+    fn maze_lookup_is_bounded() {
+        let maze = Maze::new(3, 2, vec![EMPTY, WALL, BREAKABLE, WALL, EMPTY, WALL]);
+        assert_eq!(maze.get(2, 0), Some(BREAKABLE));
+        assert_eq!(maze.get(0, 1), Some(WALL));
+        assert_eq!(maze.index(2, 1), Some(5));
+        assert_eq!(maze.get(3, 0), None);
+        assert_eq!(maze.get(0, 2), None);
+    }
+}
@@ -0,0 +1,186 @@
+//! How players walk through a maze. The server runs it to move players and
+//! the client runs the very same code to predict its own player, so the two
+//! only disagree when something the client couldn't know got in the way.
+
+use crate::{Direction, Maze, MoveKeys, Position, EMPTY, TILE_SIZE};
+
+/// Radius of the circle a player takes up, in tiles.
+pub const PLAYER_RADIUS: f32 = 0.25;
+
+/// Longest distance moved between two collision checks, in tiles, so a fast
+/// player can't pass through a corner or another player in one tick.
+const MAX_STEP: f32 = PLAYER_RADIUS / 2.0;
+
+/// Where a player at `pos` facing `angle` ends up holding `keys` for one
+/// tick in which they walk `distance` world units. Steps for which
+/// `blocked(from, to)` holds, such as into another player, are not taken.
+pub fn walk(
+    pos: Position,
+    keys: MoveKeys,
+    angle: f32,
+    distance: f32,
+    maze: &Maze,
+    blocked: impl Fn(Position, Position) -> bool,
+) -> Position {
+    match walk_direction(keys, angle) {
+        Some(direction) => slide(
+            pos,
+            Direction::new(direction.x * distance, direction.y * distance),
+            maze,
+            blocked,
+        ),
+        None => pos,
+    }
+}
+
+/// Whether stepping from `from` to `to` walks into a player standing at
+/// `other`. Players already overlapping can still move apart.
+pub fn walks_into(from: Position, to: Position, other: Position) -> bool {
+    let reach = 2.0 * PLAYER_RADIUS * TILE_SIZE;
+    let distance = |a: Position, b: Position| (a.x - b.x).hypot(a.y - b.y);
+    distance(to, other) < reach && distance(to, other) < distance(from, other)
+}
+
+/// Unit vector of where `keys` walk a player facing `angle`, or `None` when
+/// they cancel out.
+fn walk_direction(keys: MoveKeys, angle: f32) -> Option<Direction> {
+    let axis = |plus: bool, minus: bool| plus as i32 as f32 - minus as i32 as f32;
+    let forward = axis(keys.forward, keys.backward);
+    let right = axis(keys.right, keys.left);
+    let (sin, cos) = angle.sin_cos();
+    // right is a quarter turn clockwise from forward, y grows down the maze
+    let x = forward * cos - right * sin;
+    let y = forward * sin + right * cos;
+    let length = x.hypot(y);
+    (length > 0.0).then(|| Direction::new(x / length, y / length))
+}
+
+/// Where a player at `pos` ends up after moving by `delta`, in world units.
+/// Walls push the player's circle back out, so running into one at an angle
+/// slides along it.
+fn slide(
+    pos: Position,
+    delta: Direction,
+    maze: &Maze,
+    blocked: impl Fn(Position, Position) -> bool,
+) -> Position {
+    let length = delta.x.hypot(delta.y) / TILE_SIZE;
+    let steps = (length / MAX_STEP).ceil().max(1.0);
+    let step = Direction::new(delta.x / steps, delta.y / steps);
+
+    let mut pos = pos;
+    for _ in 0..steps as usize {
+        let next = push_out_of_walls(Position::new(pos.x + step.x, pos.y + step.y), maze);
+        if !blocked(pos, next) {
+            pos = next;
+        }
+    }
+    pos
+}
+
+/// Moves a player's circle off every wall tile it overlaps, along the
+/// shortest way out of each.
+fn push_out_of_walls(pos: Position, maze: &Maze) -> Position {
+    let (mut x, mut y) = (pos.x / TILE_SIZE, pos.y / TILE_SIZE);
+    let (min_x, max_x) = ((x - PLAYER_RADIUS).floor(), (x + PLAYER_RADIUS).floor());
+    let (min_y, max_y) = ((y - PLAYER_RADIUS).floor(), (y + PLAYER_RADIUS).floor());
+    for tile_y in min_y as i32..=max_y as i32 {
+        for tile_x in min_x as i32..=max_x as i32 {
+            if !is_solid(maze, tile_x, tile_y) {
+                continue;
+            }
+            let closest_x = x.clamp(tile_x as f32, tile_x as f32 + 1.0);
+            let closest_y = y.clamp(tile_y as f32, tile_y as f32 + 1.0);
+            let (away_x, away_y) = (x - closest_x, y - closest_y);
+            let distance = away_x.hypot(away_y);
+            // a centre inside the wall has no way out to push along
+            if distance > 0.0 && distance < PLAYER_RADIUS {
+                let push = (PLAYER_RADIUS - distance) / distance;
+                x += away_x * push;
+                y += away_y * push;
+            }
+        }
+    }
+    Position::new(x * TILE_SIZE, y * TILE_SIZE)
+}
+
+/// Anything but floor stops a player, and so does the outside of the maze.
+fn is_solid(maze: &Maze, x: i32, y: i32) -> bool {
+    x < 0 || y < 0 || maze.get(x as usize, y as usize) != Some(EMPTY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{at, maze};
+
+    /// Four by four tiles of floor walled in.
+    const ROOM: [&str; 6] = ["######", "#....#", "#....#", "#....#", "#....#", "######"];
+
+    const FORWARD: MoveKeys = MoveKeys {
+        forward: true,
+        backward: false,
+        left: false,
+        right: false,
+    };
+
+    #[test]
+    fn walls_stop_the_player_one_radius_away() {
+        let maze = maze(&ROOM);
+        let pos = walk(
+            at(3.0, 2.5),
+            FORWARD,
+            0.0,
+            5.0 * TILE_SIZE,
+            &maze,
+            |_, _| false,
+        );
+        assert!((pos.x / TILE_SIZE - (5.0 - PLAYER_RADIUS)).abs() < 1e-3);
+        assert!((pos.y / TILE_SIZE - 2.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn walking_into_a_wall_at_an_angle_slides_along_it() {
+        let maze = maze(&ROOM);
+        let angle = std::f32::consts::FRAC_PI_4;
+        let pos = walk(at(4.5, 1.5), FORWARD, angle, TILE_SIZE, &maze, |_, _| false);
+        assert!((pos.x / TILE_SIZE - (5.0 - PLAYER_RADIUS)).abs() < 1e-3);
+        assert!(pos.y / TILE_SIZE > 2.0);
+    }
+
+    #[test]
+    fn diagonals_are_no_faster() {
+        let keys = MoveKeys {
+            right: true,
+            ..FORWARD
+        };
+        let direction = walk_direction(keys, 0.3).unwrap();
+        assert!((direction.x.hypot(direction.y) - 1.0).abs() < 1e-6);
+        let opposite = MoveKeys {
+            backward: true,
+            ..FORWARD
+        };
+        assert_eq!(walk_direction(opposite, 0.3), None);
+    }
+
+    #[test]
+    fn players_block_each_other_but_can_part() {
+        let maze = maze(&["########", "#......#", "########"]);
+        let other = at(4.5, 1.5);
+        let blocked = |from, to| walks_into(from, to, other);
+        let pos = walk(at(1.5, 1.5), FORWARD, 0.0, 5.0 * TILE_SIZE, &maze, blocked);
+        assert!(pos.x / TILE_SIZE <= 4.5 - 2.0 * PLAYER_RADIUS);
+        assert!(pos.x / TILE_SIZE > 4.5 - 2.0 * PLAYER_RADIUS - MAX_STEP);
+
+        let overlapping = at(4.4, 1.5);
+        let away = walk(
+            overlapping,
+            FORWARD,
+            std::f32::consts::PI,
+            TILE_SIZE,
+            &maze,
+            blocked,
+        );
+        assert!(away.x < overlapping.x);
+    }
+}
@@ -0,0 +1,122 @@
+//! Rays through the maze, tile by tile. The client casts them to draw its
+//! view and the server to trace shots, both with this code, so a shot goes
+//! where the crosshair shows.
+
+use crate::{Direction, Maze, Position, EMPTY, TILE_SIZE};
+
+/// Where a ray was stopped by something other than floor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WallHit {
+    /// The tile it stopped in, as (column, row).
+    pub tile: (usize, usize),
+    /// How far it got, in world units.
+    pub distance: f32,
+    /// Whether it stepped into the tile across a column, through one of its
+    /// sides facing along x, rather than across a row.
+    pub x_side: bool,
+}
+
+/// Follows a ray from `from` along the unit vector `direction` to the first
+/// tile that isn't floor. `None` when it goes `max_distance` world units, or
+/// leaves the maze, without meeting one.
+pub fn cast_ray(
+    maze: &Maze,
+    from: Position,
+    direction: Direction,
+    max_distance: f32,
+) -> Option<WallHit> {
+    // DDA algorithm, in tiles
+    let (x, y) = (from.x / TILE_SIZE, from.y / TILE_SIZE);
+    let max_distance = max_distance / TILE_SIZE;
+
+    let unit_step_x = (1.0 + (direction.y / direction.x).powi(2)).sqrt();
+    let unit_step_y = (1.0 + (direction.x / direction.y).powi(2)).sqrt();
+    let (mut map_x, mut map_y) = (x.floor(), y.floor());
+
+    let (step_x, mut length_x) = if direction.x < 0.0 {
+        (-1.0, (x - map_x) * unit_step_x)
+    } else {
+        (1.0, (map_x + 1.0 - x) * unit_step_x)
+    };
+    let (step_y, mut length_y) = if direction.y < 0.0 {
+        (-1.0, (y - map_y) * unit_step_y)
+    } else {
+        (1.0, (map_y + 1.0 - y) * unit_step_y)
+    };
+
+    loop {
+        let x_side = length_x < length_y;
+        let distance;
+        if x_side {
+            map_x += step_x;
+            distance = length_x;
+            length_x += unit_step_x;
+        } else {
+            map_y += step_y;
+            distance = length_y;
+            length_y += unit_step_y;
+        }
+
+        if distance >= max_distance || map_x < 0.0 || map_y < 0.0 {
+            return None;
+        }
+        let tile = (map_x as usize, map_y as usize);
+        match maze.get(tile.0, tile.1) {
+            Some(EMPTY) => {}
+            Some(_) => {
+                return Some(WallHit {
+                    tile,
+                    distance: distance * TILE_SIZE,
+                    x_side,
+                })
+            }
+            None => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{at, maze};
+
+    const ROOM: [&str; 5] = ["######", "#....#", "#....#", "#....#", "######"];
+
+    #[test]
+    fn a_ray_stops_in_the_first_wall_and_tells_which_side_it_hit() {
+        let maze = maze(&ROOM);
+        let east = cast_ray(
+            &maze,
+            at(1.5, 1.5),
+            Direction::new(1.0, 0.0),
+            100.0 * TILE_SIZE,
+        );
+        assert_eq!(
+            east,
+            Some(WallHit {
+                tile: (5, 1),
+                distance: 3.5 * TILE_SIZE,
+                x_side: true,
+            })
+        );
+        let south = cast_ray(
+            &maze,
+            at(1.5, 1.5),
+            Direction::new(0.0, 1.0),
+            100.0 * TILE_SIZE,
+        );
+        assert_eq!(
+            south.map(|hit| (hit.tile, hit.x_side)),
+            Some(((1, 4), false))
+        );
+    }
+
+    #[test]
+    fn a_ray_that_runs_out_hits_nothing() {
+        let maze = maze(&ROOM);
+        let east = Direction::new(1.0, 0.0);
+        assert_eq!(cast_ray(&maze, at(1.5, 1.5), east, 3.0 * TILE_SIZE), None);
+        // from outside the maze there is nothing to hit
+        assert_eq!(cast_ray(&maze, at(7.5, 1.5), east, 100.0 * TILE_SIZE), None);
+    }
+}
@@ -0,0 +1,40 @@
+//! Fixtures for tests, in this crate and in the ones built on it, which turn
+//! on the `testing` feature for theirs.
+
+use crate::{GameState, Maze, Player, Position, RoundPhase, BREAKABLE, EMPTY, TILE_SIZE, WALL};
+
+/// A maze drawn with one character per tile, the way map files are: `#`
+/// wall, `%` breakable wall and `.` floor.
+pub fn maze(rows: &[&str]) -> Maze {
+    let width = rows[0].len();
+    let tiles = rows
+        .iter()
+        .inspect(|row| assert_eq!(row.len(), width, "row {:?} is not {} wide", row, width))
+        .flat_map(|row| row.chars())
+        .map(|c| match c {
+            '#' => WALL,
+            '%' => BREAKABLE,
+            '.' => EMPTY,
+            other => panic!("unknown tile {:?}", other),
+        })
+        .collect();
+    Maze::new(width, rows.len(), tiles)
+}
+
+/// The position `x` tiles right and `y` tiles down from the top left corner.
+pub fn at(x: f32, y: f32) -> Position {
+    Position::new(x * TILE_SIZE, y * TILE_SIZE)
+}
+
+/// The state of round 1 being played on `maze` by `players`, as of `tick`.
+pub fn state(maze: Maze, tick: u32, players: Vec<Player>) -> GameState {
+    GameState {
+        players,
+        maze,
+        seed: None,
+        round: 1,
+        tick,
+        phase: RoundPhase::Playing,
+        events: Vec::new(),
+    }
+}
@@ -0,0 +1,23 @@
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Largest payload a single UDP datagram can carry; receive buffers use it.
+pub const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+pub type DecodeError = bincode::Error;
+pub type EncodeError = bincode::Error;
+
+// Varint integers keep tile indices, ids and counters down to a byte or two.
+fn options() -> impl Options {
+    bincode::DefaultOptions::new().with_limit(MAX_DATAGRAM_SIZE as u64)
+}
+
+/// Fails for a message that doesn't fit in a datagram.
+pub fn encode<T: Serialize>(message: &T) -> Result<Vec<u8>, EncodeError> {
+    options().serialize(message)
+}
+
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, DecodeError> {
+    options().deserialize(bytes)
+}
@@ -0,0 +1,298 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use maze_wars_protocol::{
+    GameState, MoveKeys, Player, PlayerId, ServerMessage, SessionToken, SnapshotHistory, StateDelta,
+};
+
+/// Moves held back for later ticks at most; more means the client is
+/// sending faster than it should, and the oldest are dropped.
+const MAX_QUEUED_MOVES: usize = 32;
+
+/// Moves left waiting once a tick has used up its credit. A client whose
+/// moves keep arriving faster than the server ticks, such as while ticks run
+/// late, would otherwise fall further behind for good.
+const MAX_MOVE_BACKLOG: usize = 2;
+
+/// Shots waiting for the next tick at most; a client can't honestly fire
+/// more than a few between two ticks.
+const MAX_QUEUED_SHOTS: usize = 8;
+
+/// Moves a client can save up while it sends none, to catch up when late
+/// packets arrive together.
+const MAX_MOVE_CREDIT: u32 = 4;
+
+/// States remembered per client to time their acks against, a couple of
+/// seconds' worth.
+const SENT_HISTORY_LEN: usize = 64;
+
+/// A move from the client waiting for a tick to be made in.
+#[derive(Clone, Copy)]
+pub struct QueuedMove {
+    pub sequence: u32,
+    pub keys: MoveKeys,
+    pub angle: f32,
+}
+
+/// Per-address connection state kept by the server.
+pub struct Client {
+    pub player_id: PlayerId,
+    pub session: SessionToken,
+    /// Moves not made yet, oldest first.
+    moves: VecDeque<QueuedMove>,
+    /// Angles of the shots fired since the last tick, oldest first.
+    pub shots: Vec<f32>,
+    /// Moves that may still be applied; every tick adds one, so a client
+    /// never walks faster than one move a tick for long.
+    move_credit: u32,
+    /// Newest state sequence the client confirmed it applied.
+    acked_sequence: Option<u32>,
+    /// Tick on which anything was last received from the client.
+    pub last_seen_tick: u32,
+    /// When the latest states were sent, by sequence.
+    sent: VecDeque<(u32, Instant)>,
+    /// Smoothed round trip time in milliseconds.
+    pub ping: u32,
+}
+
+impl Client {
+    pub fn new(player_id: PlayerId, session: SessionToken, tick: u32) -> Self {
+        Self {
+            player_id,
+            session,
+            moves: VecDeque::new(),
+            shots: Vec::new(),
+            move_credit: 0,
+            acked_sequence: None,
+            last_seen_tick: tick,
+            sent: VecDeque::with_capacity(SENT_HISTORY_LEN),
+            ping: 0,
+        }
+    }
+
+    pub fn queue_move(&mut self, queued: QueuedMove) {
+        if self.moves.len() == MAX_QUEUED_MOVES {
+            self.moves.pop_front();
+        }
+        self.moves.push_back(queued);
+    }
+
+    pub fn queue_shot(&mut self, angle: f32) {
+        if self.shots.len() < MAX_QUEUED_SHOTS {
+            self.shots.push(angle);
+        }
+    }
+
+    pub fn add_move_credit(&mut self) {
+        self.move_credit = (self.move_credit + 1).min(MAX_MOVE_CREDIT);
+    }
+
+    /// The oldest waiting move, if there is credit left this tick to make it.
+    pub fn next_move(&mut self) -> Option<QueuedMove> {
+        if self.move_credit == 0 {
+            return None;
+        }
+        let queued = self.moves.pop_front()?;
+        self.move_credit -= 1;
+        Some(queued)
+    }
+
+    /// Forgets the oldest moves still waiting beyond a short backlog. The
+    /// client corrects its prediction once a state shows where it really is.
+    pub fn drop_move_backlog(&mut self) {
+        let excess = self.moves.len().saturating_sub(MAX_MOVE_BACKLOG);
+        self.moves.drain(..excess);
+    }
+
+    pub fn acknowledge(&mut self, sequence: u32) {
+        if self.acked_sequence.is_none_or(|acked| sequence > acked) {
+            self.acked_sequence = Some(sequence);
+            self.measure_ping(sequence);
+        }
+    }
+
+    pub fn reset_acknowledgements(&mut self) {
+        self.acked_sequence = None;
+        self.sent.clear();
+    }
+
+    /// Remembers when the state with `sequence` went out.
+    pub fn sent(&mut self, sequence: u32) {
+        if self.sent.len() == SENT_HISTORY_LEN {
+            self.sent.pop_front();
+        }
+        self.sent.push_back((sequence, Instant::now()));
+    }
+
+    fn measure_ping(&mut self, sequence: u32) {
+        let Some(&(_, sent_at)) = self.sent.iter().find(|(seq, _)| *seq == sequence) else {
+            return;
+        };
+        let sample = sent_at.elapsed().as_millis() as u32;
+        // smoothed so one late packet doesn't make the scoreboard jump
+        self.ping = if self.ping == 0 {
+            sample
+        } else {
+            (self.ping * 7 + sample) / 8
+        };
+    }
+
+    /// A delta against the last acknowledged state when we still have it,
+    /// otherwise the full snapshot.
+    pub fn state_message(
+        &self,
+        history: &SnapshotHistory,
+        sequence: u32,
+        state: &GameState,
+    ) -> ServerMessage {
+        self.acked_sequence
+            .and_then(|acked| {
+                let base = history.get(acked)?;
+                StateDelta::between(acked, base, sequence, state)
+            })
+            .map(ServerMessage::Delta)
+            .unwrap_or_else(|| ServerMessage::Snapshot {
+                sequence,
+                state: state.clone(),
+            })
+    }
+}
+
+/// Someone whose packets were refused lately.
+pub struct RejectedSender {
+    pub count: u32,
+    /// Tick on which the latest of them arrived.
+    pub last_tick: u32,
+}
+
+/// A player who left recently and can still be reclaimed with their token.
+pub struct DepartedSession {
+    pub player: Player,
+    pub round: usize,
+    pub left_at_tick: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maze_wars_protocol::testing::{maze, state};
+    use std::time::Duration;
+
+    fn client() -> Client {
+        Client::new(0, 0, 0)
+    }
+
+    fn queue_moves(client: &mut Client, sequences: std::ops::RangeInclusive<u32>) {
+        for sequence in sequences {
+            client.queue_move(QueuedMove {
+                sequence,
+                keys: MoveKeys::default(),
+                angle: 0.0,
+            });
+        }
+    }
+
+    fn next_sequences(client: &mut Client) -> Vec<u32> {
+        std::iter::from_fn(|| client.next_move())
+            .map(|queued| queued.sequence)
+            .collect()
+    }
+
+    #[test]
+    fn a_tick_of_credit_makes_one_move() {
+        let mut client = client();
+        queue_moves(&mut client, 1..=3);
+        assert_eq!(next_sequences(&mut client), []);
+        client.add_move_credit();
+        assert_eq!(next_sequences(&mut client), [1]);
+        client.add_move_credit();
+        assert_eq!(next_sequences(&mut client), [2]);
+    }
+
+    #[test]
+    fn credit_saved_up_is_capped() {
+        let mut client = client();
+        for _ in 0..10 {
+            client.add_move_credit();
+        }
+        queue_moves(&mut client, 1..=10);
+        assert_eq!(next_sequences(&mut client), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn only_a_short_backlog_waits_for_later_ticks() {
+        let mut client = client();
+        queue_moves(&mut client, 1..=5);
+        client.add_move_credit();
+        assert_eq!(next_sequences(&mut client), [1]);
+        client.drop_move_backlog();
+        client.add_move_credit();
+        client.add_move_credit();
+        assert_eq!(next_sequences(&mut client), [4, 5]);
+    }
+
+    #[test]
+    fn queues_keep_the_newest_moves_and_the_oldest_shots() {
+        let mut client = client();
+        let extra = 3;
+        queue_moves(&mut client, 1..=(MAX_QUEUED_MOVES as u32 + extra));
+        assert_eq!(client.moves.len(), MAX_QUEUED_MOVES);
+        assert_eq!(client.moves.front().unwrap().sequence, extra + 1);
+
+        for shot in 0..MAX_QUEUED_SHOTS + 3 {
+            client.queue_shot(shot as f32);
+        }
+        assert_eq!(client.shots.len(), MAX_QUEUED_SHOTS);
+        assert_eq!(client.shots[0], 0.0);
+    }
+
+    #[test]
+    fn acks_measure_a_smoothed_ping() {
+        let mut client = client();
+        let now = Instant::now();
+        client.sent.push_back((1, now - Duration::from_millis(80)));
+        client.sent.push_back((2, now - Duration::from_millis(160)));
+        client.sent.push_back((3, now - Duration::from_millis(400)));
+
+        client.acknowledge(1);
+        let first = client.ping;
+        assert!((80..120).contains(&first), "{}", first);
+        // the second sample only moves it an eighth of the way
+        client.acknowledge(2);
+        let second = client.ping;
+        assert!(
+            second > first && second < first + 20,
+            "{} {}",
+            first,
+            second
+        );
+        // an ack older than the newest one is ignored
+        client.acknowledge(1);
+        assert_eq!(client.ping, second);
+        assert_eq!(client.acked_sequence, Some(2));
+    }
+
+    #[test]
+    fn states_go_out_as_deltas_against_the_last_ack() {
+        let mut client = client();
+        let mut history = SnapshotHistory::default();
+        let first = state(maze(&["###", "#.#", "###"]), 1, Vec::new());
+        history.push(1, first.clone());
+        let mut second = first.clone();
+        second.tick = 2;
+
+        let snapshot = |message: &ServerMessage| matches!(message, ServerMessage::Snapshot { .. });
+        assert!(snapshot(&client.state_message(&history, 2, &second)));
+        client.acknowledge(1);
+        assert!(matches!(
+            client.state_message(&history, 2, &second),
+            ServerMessage::Delta(StateDelta { base: 1, .. })
+        ));
+        // a state we no longer have can't be a base
+        client.acknowledge(5);
+        assert!(snapshot(&client.state_message(&history, 6, &second)));
+        // nor can anything after the client moved
+        client.reset_acknowledgements();
+        assert!(snapshot(&client.state_message(&history, 6, &second)));
+    }
+}
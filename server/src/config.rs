@@ -0,0 +1,457 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use clap::Parser;
+use maze_wars_protocol::MazeSeed;
+
+use crate::maze::{Difficulty, GeneratorKind, PlaylistEntry, MAX_MAZE_SIZE, MIN_MAZE_SIZE};
+use serde::{Deserialize, Deserializer};
+
+/// Fastest a player may walk, in tiles a second.
+const MAX_MOVE_SPEED: f32 = 20.0;
+
+/// Most ticks a second the server runs. Durations are counted in ticks, so
+/// this also keeps the longest of them well within a `u32`.
+const MAX_TICK_RATE: u32 = 120;
+
+/// Longest break between two rounds, in seconds.
+const MAX_INTERMISSION_SECONDS: u32 = 600;
+
+/// Maze Wars game server.
+///
+/// Settings come from the defaults, then the config file, then the flags
+/// given here, each overriding the one before.
+#[derive(Parser, Debug)]
+#[command(version)]
+struct Args {
+    /// UDP port to listen on
+    port: Option<u16>,
+    /// TOML file with the server settings
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// Simulation steps per second
+    #[arg(long)]
+    tick_rate: Option<u32>,
+    /// Score that wins a round
+    #[arg(long)]
+    score_limit: Option<u32>,
+    /// Seconds between the end of a round and the start of the next one
+    #[arg(long)]
+    intermission_seconds: Option<u32>,
+    /// How many tiles a shot travels
+    #[arg(long)]
+    shot_range: Option<u32>,
+    /// Tiles a second players walk while a movement key is held
+    #[arg(long)]
+    move_speed: Option<f32>,
+    /// Maze width in tiles, before the difficulty's size scaling
+    #[arg(long)]
+    maze_width: Option<usize>,
+    /// Maze height in tiles, before the difficulty's size scaling
+    #[arg(long)]
+    maze_height: Option<usize>,
+    /// Rounds played before starting over from the first maze; by default
+    /// the rounds and their difficulty go on forever
+    #[arg(long)]
+    rounds: Option<usize>,
+    /// What the rounds take turns being played on: a generator's name or
+    /// map:NAME; repeat for several
+    #[arg(long = "play", value_name = "ENTRY")]
+    playlist: Vec<PlaylistEntry>,
+    /// Directory the maps in the playlist are loaded from
+    #[arg(long)]
+    maps_dir: Option<PathBuf>,
+    /// Maze seed to use whenever a round is played, as ROUND=SEED
+    #[arg(long = "seed", value_name = "ROUND=SEED", value_parser = parse_round_seed)]
+    seeds: Vec<(usize, MazeSeed)>,
+}
+
+/// Rules and settings of a running server.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub port: u16,
+    pub tick_rate: u32,
+    pub score_limit: u32,
+    pub intermission_seconds: u32,
+    pub shot_range: u32,
+    /// Tiles a second a player walks.
+    pub move_speed: f32,
+    pub maze_width: usize,
+    pub maze_height: usize,
+    /// Rounds played before starting over from round 1, if ever.
+    pub rounds: Option<usize>,
+    /// Generators and maps the rounds take turns with, starting from the
+    /// first.
+    pub playlist: Vec<PlaylistEntry>,
+    pub maps_dir: PathBuf,
+    /// Seeds forced for some rounds, by round number; the other rounds get
+    /// a random one.
+    #[serde(deserialize_with = "deserialize_seeds")]
+    pub seeds: BTreeMap<usize, MazeSeed>,
+    /// How the mazes change from one round to the next.
+    pub difficulty: Difficulty,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 8080,
+            tick_rate: 30,
+            score_limit: 5,
+            intermission_seconds: 5,
+            shot_range: 6,
+            move_speed: 4.0,
+            maze_width: 24,
+            maze_height: 24,
+            rounds: None,
+            playlist: vec![
+                PlaylistEntry::Generated(GeneratorKind::Corridors),
+                PlaylistEntry::Generated(GeneratorKind::Backtracker),
+                PlaylistEntry::Generated(GeneratorKind::Rooms),
+            ],
+            maps_dir: PathBuf::from("maps"),
+            seeds: BTreeMap::new(),
+            difficulty: Difficulty::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Builds the config from the command line and the file it points to.
+    /// Exits with a usage message on bad arguments.
+    pub fn load() -> Result<Self, String> {
+        Self::from_args(Args::parse())
+    }
+
+    fn from_args(args: Args) -> Result<Self, String> {
+        let mut config = match &args.config {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+                toml::from_str(&text)
+                    .map_err(|e| format!("bad config {}: {}", path.display(), e))?
+            }
+            None => Config::default(),
+        };
+
+        if let Some(port) = args.port {
+            config.port = port;
+        }
+        if let Some(tick_rate) = args.tick_rate {
+            config.tick_rate = tick_rate;
+        }
+        if let Some(score_limit) = args.score_limit {
+            config.score_limit = score_limit;
+        }
+        if let Some(seconds) = args.intermission_seconds {
+            config.intermission_seconds = seconds;
+        }
+        if let Some(shot_range) = args.shot_range {
+            config.shot_range = shot_range;
+        }
+        if let Some(speed) = args.move_speed {
+            config.move_speed = speed;
+        }
+        if let Some(width) = args.maze_width {
+            config.maze_width = width;
+        }
+        if let Some(height) = args.maze_height {
+            config.maze_height = height;
+        }
+        if args.rounds.is_some() {
+            config.rounds = args.rounds;
+        }
+        if !args.playlist.is_empty() {
+            config.playlist = args.playlist;
+        }
+        if let Some(dir) = args.maps_dir {
+            config.maps_dir = dir;
+        }
+        config.seeds.extend(args.seeds);
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// What `round` is played on.
+    pub fn playlist_entry(&self, round: usize) -> &PlaylistEntry {
+        &self.playlist[(round - 1) % self.playlist.len()]
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_TICK_RATE).contains(&self.tick_rate) {
+            return Err(format!(
+                "tick_rate must be between 1 and {}, got {}",
+                MAX_TICK_RATE, self.tick_rate
+            ));
+        }
+        if self.score_limit == 0 {
+            return Err(String::from("score_limit must be at least 1"));
+        }
+        if self.intermission_seconds > MAX_INTERMISSION_SECONDS {
+            return Err(format!(
+                "intermission_seconds must be at most {}, got {}",
+                MAX_INTERMISSION_SECONDS, self.intermission_seconds
+            ));
+        }
+        if !(self.move_speed > 0.0 && self.move_speed <= MAX_MOVE_SPEED) {
+            return Err(format!(
+                "move_speed must be above 0 and at most {}, got {}",
+                MAX_MOVE_SPEED, self.move_speed
+            ));
+        }
+        for (name, size) in [
+            ("maze_width", self.maze_width),
+            ("maze_height", self.maze_height),
+        ] {
+            if !(MIN_MAZE_SIZE..=MAX_MAZE_SIZE).contains(&size) {
+                return Err(format!(
+                    "{} must be between {} and {}, got {}",
+                    name, MIN_MAZE_SIZE, MAX_MAZE_SIZE, size
+                ));
+            }
+        }
+        if self.playlist.is_empty() {
+            return Err(String::from("playlist must name at least one maze"));
+        }
+        if self.rounds == Some(0) {
+            return Err(String::from("rounds must be at least 1"));
+        }
+        if self.seeds.contains_key(&0) {
+            return Err(String::from("seed given for round 0, rounds start at 1"));
+        }
+        if let Some(rounds) = self.rounds {
+            if let Some(round) = self.seeds.keys().find(|&&round| round > rounds) {
+                return Err(format!(
+                    "seed given for round {}, but rounds run from 1 to {}",
+                    round, rounds
+                ));
+            }
+        }
+        self.difficulty.validate()?;
+        Ok(())
+    }
+}
+
+fn parse_round_seed(arg: &str) -> Result<(usize, MazeSeed), String> {
+    let (round, seed) = arg
+        .split_once('=')
+        .ok_or_else(|| String::from("expected ROUND=SEED"))?;
+    let round = round
+        .parse()
+        .map_err(|e| format!("bad round {:?}: {}", round, e))?;
+    let seed = seed
+        .parse()
+        .map_err(|e| format!("bad seed {:?}: {}", seed, e))?;
+    Ok((round, seed))
+}
+
+// TOML table keys are always strings, so `[seeds]` keys are parsed here.
+fn deserialize_seeds<'de, D>(deserializer: D) -> Result<BTreeMap<usize, MazeSeed>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    BTreeMap::<String, MazeSeed>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(round, seed)| {
+            round
+                .parse()
+                .map(|round| (round, seed))
+                .map_err(|_| serde::de::Error::custom(format!("bad round number {:?}", round)))
+        })
+        .collect()
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "  port: {}", self.port)?;
+        writeln!(f, "  tick rate: {}/s", self.tick_rate)?;
+        writeln!(f, "  score limit: {}", self.score_limit)?;
+        writeln!(f, "  intermission: {}s", self.intermission_seconds)?;
+        writeln!(f, "  shot range: {} tiles", self.shot_range)?;
+        writeln!(f, "  move speed: {} tiles/s", self.move_speed)?;
+        writeln!(f, "  maze size: {}x{}", self.maze_width, self.maze_height)?;
+        match self.rounds {
+            Some(rounds) => writeln!(f, "  rounds per cycle: {}", rounds)?,
+            None => writeln!(f, "  rounds per cycle: unlimited")?,
+        }
+        writeln!(f, "{}", self.difficulty)?;
+        let playlist: Vec<String> = self.playlist.iter().map(|e| e.to_string()).collect();
+        write!(f, "  playlist: {}", playlist.join(", "))?;
+        if self
+            .playlist
+            .iter()
+            .any(|entry| matches!(entry, PlaylistEntry::Map(_)))
+        {
+            write!(f, "\n  maps: {}", self.maps_dir.display())?;
+        }
+        for (round, seed) in &self.seeds {
+            write!(f, "\n  round {} seed: {}", round, seed)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/maze-wars.example.toml");
+
+    fn load(args: &[&str]) -> Result<Config, String> {
+        let args = Args::try_parse_from(["maze-wars"].iter().chain(args)).unwrap();
+        Config::from_args(args)
+    }
+
+    fn parse(toml: &str) -> Result<Config, String> {
+        toml::from_str(toml).map_err(|e| e.to_string())
+    }
+
+    /// The error `validate` gives for the defaults changed by `change`.
+    fn invalid(change: impl FnOnce(&mut Config)) -> String {
+        let mut config = Config::default();
+        change(&mut config);
+        config.validate().unwrap_err()
+    }
+
+    #[test]
+    fn flags_override_the_file_which_overrides_the_defaults() {
+        let config = load(&[
+            "9000", "--config", EXAMPLE, "--rounds", "2", "--seed", "2=7",
+        ])
+        .unwrap();
+        // from the flags
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.rounds, Some(2));
+        assert_eq!(config.seeds, BTreeMap::from([(2, 7)]));
+        // from the file
+        assert_eq!(config.playlist.len(), 4);
+        assert_eq!(
+            config.playlist[2],
+            PlaylistEntry::Map(String::from("arena"))
+        );
+        // left at the defaults
+        assert_eq!(config.score_limit, Config::default().score_limit);
+        assert_eq!(config.tick_rate, Config::default().tick_rate);
+    }
+
+    #[test]
+    fn the_defaults_and_the_example_are_valid() {
+        assert!(Config::default().validate().is_ok());
+        assert!(load(&["--config", EXAMPLE]).is_ok());
+    }
+
+    #[test]
+    fn invalid_settings_from_the_flags_are_caught() {
+        let error = load(&["--tick-rate", "0"]).unwrap_err();
+        assert!(error.contains("tick_rate"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_keys_are_refused() {
+        let error = parse("tick_rate = 30\nspeed = 4.0\n").unwrap_err();
+        assert!(error.contains("unknown field `speed`"), "{}", error);
+    }
+
+    #[test]
+    fn seeds_are_keyed_by_round_number() {
+        let config = parse("[seeds]\n2 = 1234\n10 = 5\n").unwrap();
+        assert_eq!(config.seeds, BTreeMap::from([(2, 1234), (10, 5)]));
+        let error = parse("[seeds]\nsecond = 1234\n").unwrap_err();
+        assert!(error.contains("bad round number \"second\""), "{}", error);
+    }
+
+    #[test]
+    fn round_seeds_are_parsed_from_flags() {
+        assert_eq!(parse_round_seed("3=42"), Ok((3, 42)));
+        assert_eq!(
+            parse_round_seed("3"),
+            Err(String::from("expected ROUND=SEED"))
+        );
+        assert!(parse_round_seed("third=42")
+            .unwrap_err()
+            .starts_with("bad round \"third\""));
+        assert!(parse_round_seed("3=-1")
+            .unwrap_err()
+            .starts_with("bad seed \"-1\""));
+    }
+
+    #[test]
+    fn tick_rate_and_intermission_are_bounded() {
+        assert_eq!(
+            invalid(|c| c.tick_rate = 0),
+            "tick_rate must be between 1 and 120, got 0"
+        );
+        assert_eq!(
+            invalid(|c| c.tick_rate = MAX_TICK_RATE + 1),
+            "tick_rate must be between 1 and 120, got 121"
+        );
+        assert_eq!(
+            invalid(|c| c.intermission_seconds = MAX_INTERMISSION_SECONDS + 1),
+            "intermission_seconds must be at most 600, got 601"
+        );
+    }
+
+    #[test]
+    fn rules_are_checked() {
+        assert_eq!(
+            invalid(|c| c.score_limit = 0),
+            "score_limit must be at least 1"
+        );
+        for speed in [0.0, f32::NAN, MAX_MOVE_SPEED + 1.0] {
+            let error = invalid(|c| c.move_speed = speed);
+            assert!(error.starts_with("move_speed must be above 0"), "{}", error);
+        }
+        assert_eq!(
+            invalid(|c| c.maze_width = MIN_MAZE_SIZE - 1),
+            format!(
+                "maze_width must be between {} and {}, got {}",
+                MIN_MAZE_SIZE,
+                MAX_MAZE_SIZE,
+                MIN_MAZE_SIZE - 1
+            )
+        );
+        assert_eq!(
+            invalid(|c| c.maze_height = MAX_MAZE_SIZE + 1),
+            format!(
+                "maze_height must be between {} and {}, got {}",
+                MIN_MAZE_SIZE,
+                MAX_MAZE_SIZE,
+                MAX_MAZE_SIZE + 1
+            )
+        );
+    }
+
+    #[test]
+    fn rounds_playlist_and_seeds_are_checked() {
+        assert_eq!(
+            invalid(|c| c.playlist.clear()),
+            "playlist must name at least one maze"
+        );
+        assert_eq!(invalid(|c| c.rounds = Some(0)), "rounds must be at least 1");
+        assert_eq!(
+            invalid(|c| {
+                c.seeds.insert(0, 1);
+            }),
+            "seed given for round 0, rounds start at 1"
+        );
+        assert_eq!(
+            invalid(|c| {
+                c.rounds = Some(3);
+                c.seeds.insert(4, 1);
+            }),
+            "seed given for round 4, but rounds run from 1 to 3"
+        );
+    }
+
+    #[test]
+    fn the_difficulty_is_checked() {
+        let error = parse("[difficulty.openness]\nstart = 0.5\nstep = 0.1\nlimit = 0.2\n")
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(error.contains("steps away from its limit"), "{}", error);
+    }
+}
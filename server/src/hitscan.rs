@@ -0,0 +1,56 @@
+use macroquad::prelude as mq;
+use maze_wars_protocol::{cast_ray, Direction, Maze, Position};
+
+use crate::spawn::Tile;
+
+/// How far a shot from `from` along the unit vector `direction` gets before
+/// a wall stops it, up to `range`, all in world units, and the wall tile it
+/// stopped in. The ray is cast the way the client casts its view, so a shot
+/// goes where the crosshair shows.
+pub fn trace(maze: &Maze, from: Position, direction: mq::Vec2, range: f32) -> (f32, Option<Tile>) {
+    match cast_ray(maze, from, Direction::new(direction.x, direction.y), range) {
+        Some(hit) => (hit.distance, Some(hit.tile)),
+        None => (range, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maze_wars_protocol::testing::{at, maze};
+    use maze_wars_protocol::TILE_SIZE;
+
+    /// Six by six tiles of floor walled in.
+    const ROOM: [&str; 8] = [
+        "########", "#......#", "#......#", "#......#", "#......#", "#......#", "#......#",
+        "########",
+    ];
+
+    #[test]
+    fn a_diagonal_shot_stops_at_the_first_wall() {
+        // two across for every one down passes (4, 3) and enters (5, 3) at x = 5
+        let direction = mq::Vec2::new(2.0, 1.0).normalize();
+        let mut rows = ROOM;
+        rows[3] = "#....#.#";
+        let (reach, wall) = trace(&maze(&rows), at(1.5, 1.5), direction, 20.0 * TILE_SIZE);
+        assert_eq!(wall, Some((5, 3)));
+        let expected = 3.5 * 1.25f32.sqrt();
+        assert!((reach / TILE_SIZE - expected).abs() < 1e-4);
+    }
+
+    #[test]
+    fn a_shot_ends_at_its_range() {
+        let direction = mq::Vec2::new(1.0, 0.0);
+        let (reach, wall) = trace(&maze(&ROOM), at(1.5, 1.5), direction, 2.0 * TILE_SIZE);
+        assert_eq!(wall, None);
+        assert_eq!(reach, 2.0 * TILE_SIZE);
+    }
+
+    #[test]
+    fn a_shot_straight_up_stops_at_the_border() {
+        let direction = mq::Vec2::new(0.0, -1.0);
+        let (reach, wall) = trace(&maze(&ROOM), at(3.5, 3.5), direction, 20.0 * TILE_SIZE);
+        assert_eq!(wall, Some((3, 0)));
+        assert!((reach / TILE_SIZE - 2.5).abs() < 1e-4);
+    }
+}
@@ -1,247 +1,780 @@
+mod client;
+mod config;
+mod hitscan;
 mod maze;
+mod occupancy;
 mod player;
+mod round;
+mod spawn;
 
-use crate::maze::select_maze;
-use crate::player::{Player, Position};
+use crate::client::{Client, DepartedSession, QueuedMove, RejectedSender};
+use crate::config::Config;
+use crate::maze::{load_maps, select_maze, DesignedMap, PlaylistEntry};
+use crate::occupancy::Occupancy;
+use crate::player::PlayerInput;
+use crate::round::RoundState;
+use crate::spawn::{choose_spawn, Tile};
+use maze_wars_protocol::{
+    decode, encode, Action, ClientMessage, EventKind, GameEvent, GameState, JoinResponse,
+    LeaveReason, Maze, MazeSeed, Player, PlayerId, Position, RoundPhase, ServerMessage,
+    SessionToken, SnapshotHistory, Weapon, MAX_EVENTS, MAX_NAME_LENGTH, MAX_PLAYERS,
+    PROTOCOL_VERSION, TILE_SIZE,
+};
+use rand::seq::SliceRandom;
 use rand::*;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::Duration;
 use tokio::net::UdpSocket;
+use tokio::time::MissedTickBehavior;
 
-pub const MAZE_WIDTH: usize = 24;
-pub const MAZE_HEIGHT: usize = MAZE_WIDTH;
+/// Seconds of silence after which a client is considered gone.
+const CLIENT_TIMEOUT: u32 = 10;
 
-pub const EMPTY: u8 = 0;
-pub const PLAYER: u8 = 1;
-pub const WALL: u8 = 2;
+/// Seconds an event stays in the broadcast state.
+const EVENT_LIFETIME: u32 = 5;
 
-pub const BREAKABLE: u8 = 3;
+/// Seconds a player who left can still reclaim their slot and score.
+const SESSION_GRACE_PERIOD: u32 = 60;
 
-const TILE_SIZE: f32 = 64.0 / 3.0;
+/// Seconds a sender of refused packets is remembered after their last one.
+const REJECTION_MEMORY: u32 = 60;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct PlayerUpdate {
-    id: usize,
-    action: String,
-}
-#[derive(Serialize, Deserialize)]
-struct GameState {
-    players: Vec<Player>,
-    maze: Vec<u8>,
-    round: usize,
-    new_round_state: bool,
-    winner: String,
-}
+/// Senders of refused packets remembered at most. Addresses can be spoofed,
+/// so past this new ones are refused without being counted or logged.
+const MAX_REJECTED_SENDERS: usize = 1024;
 
-impl GameState {
-    fn new() -> Self {
-        Self {
-            players: Vec::new(),
-            maze: select_maze(1),
-            round: 1,
-            new_round_state: false,
-            winner: String::from(""),
-        }
+fn new_game_state(maze: Maze, seed: Option<MazeSeed>) -> GameState {
+    GameState {
+        players: Vec::new(),
+        maze,
+        seed,
+        round: 1,
+        tick: 0,
+        phase: RoundPhase::Playing,
+        events: Vec::new(),
     }
 }
 
 #[tokio::main]
 async fn main() {
-    let args = std::env::args().collect::<Vec<String>>();
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("maze-wars: {}", e);
+        std::process::exit(2);
+    });
+    let tick_rate = config.tick_rate;
+    let maps = load_playlist_maps(&config).unwrap_or_else(|e| {
+        eprintln!("maze-wars: {}", e);
+        std::process::exit(2);
+    });
 
-    let addr = if args.len() == 2 {
-        format!("0.0.0.0:{}", args[1])
-    } else {
-        "0.0.0.0:8080".to_string()
-    };
+    let addr = format!("0.0.0.0:{}", config.port);
     let socket = UdpSocket::bind(addr.clone()).await.unwrap();
-    println!("Server running on {}", addr);
+    println!("Server running on {} with\n{}", addr, config);
 
-    let mut clients: HashMap<SocketAddr, Player> = HashMap::new();
+    let mut clients: HashMap<SocketAddr, Client> = HashMap::new();
+    // players who left recently and may still come back with their token
+    let mut departed: HashMap<SessionToken, DepartedSession> = HashMap::new();
+    // ids are never reused, even after a player leaves
+    let mut next_player_id: PlayerId = 0;
+    // packets we refused, by sender, to spot broken or malicious clients
+    let mut rejected_packets: HashMap<SocketAddr, RejectedSender> = HashMap::new();
     let mut buf = [0u8; 1024];
-    let mut game_state = GameState::new();
-    let placeholder_addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0));
+    let (maze, seed, mut spawns) = round_maze(&config, &maps, 1);
+    let mut game_state = new_game_state(maze, seed);
+    // states already broadcast, keyed by tick, so clients get deltas against what they acked
+    let mut history = SnapshotHistory::default();
+    let mut round_state = RoundState::Playing;
+
+    let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / tick_rate as f64));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
     loop {
-        let (len, client_addr) = match socket.try_recv_from(&mut buf) {
-            Ok((len, client_addr)) => (len, client_addr),
-            Err(_) => (0, placeholder_addr),
-        };
-        let msg = String::from_utf8_lossy(&buf[..len]);
-
-        //bool to indicate when to send initial game state, after this we only send when a player is updated
-        let mut send_initial_gs = false;
-
-        if msg.starts_with("new_connection") {
-            // Example: "new_connection:foo" -> "foo"
-            let player_name = msg
-                .split_once(':')
-                .map(|(_, name)| name.trim())
-                .unwrap_or("");
-
-            if let std::collections::hash_map::Entry::Vacant(e) = clients.entry(client_addr) {
-                send_initial_gs = true;
-                let id = game_state.players.len().to_string();
-                println!(
-                    "New player connected with ID: {}, name: {}",
-                    id, player_name
-                );
-                socket.send_to(id.as_bytes(), client_addr).await.unwrap();
-                let mut rng = thread_rng();
-                let new_pos: Position;
-                loop {
-                    let new_x_tile = rng.gen_range(0..MAZE_WIDTH);
-                    let new_y_tile = rng.gen_range(0..MAZE_HEIGHT);
-                    let idx = new_y_tile * MAZE_WIDTH + new_x_tile;
-
-                    if game_state.maze[idx] == EMPTY {
-                        // Calculate the center of the tile for the new position
-                        new_pos = Position {
-                            x: new_x_tile as f32 * TILE_SIZE + TILE_SIZE / 2.0,
-                            y: new_y_tile as f32 * TILE_SIZE + TILE_SIZE / 2.0,
-                        };
-
-                        // Set the tile to 1 (player)
-                        game_state.maze[idx] = PLAYER;
-                        break;
-                    }
+        ticker.tick().await;
+        game_state.tick += 1;
+
+        // Drain everything that arrived since the last tick
+        loop {
+            let (len, client_addr) = match socket.try_recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    println!("Failed to receive: {}", e);
+                    break;
+                }
+            };
+            let message = match decode::<ClientMessage>(&buf[..len]) {
+                Ok(message) => message,
+                Err(e) => {
+                    reject_packet(
+                        &mut rejected_packets,
+                        client_addr,
+                        &e.to_string(),
+                        game_state.tick,
+                    );
+                    continue;
+                }
+            };
+
+            // Any message counts as a heartbeat
+            match clients.get_mut(&client_addr) {
+                Some(client) => client.last_seen_tick = game_state.tick,
+                None if matches!(
+                    message,
+                    ClientMessage::Update(_) | ClientMessage::Ack { .. }
+                ) =>
+                {
+                    // Probably a known client behind a new address, have it join again
+                    send(&socket, &ServerMessage::Rejoin, client_addr).await;
+                    continue;
                 }
-                let new_player =
-                    Player::new(new_pos, game_state.players.len(), player_name.to_string());
-                e.insert(new_player.clone());
-                game_state.players.push(new_player.clone());
+                None => {}
             }
-        } else if let Ok(update) = serde_json::from_str::<PlayerUpdate>(&msg) {
-            // Update players action in the vector of players
-            for player in game_state.players.iter_mut() {
-                if player.id == update.id && update.action != "ping" {
-                    player.action = update.action.clone();
+
+            match message {
+                ClientMessage::Join(join) => {
+                    if join.version != PROTOCOL_VERSION {
+                        println!(
+                            "Rejected {} ({}): protocol version {} != {}",
+                            join.name, client_addr, join.version, PROTOCOL_VERSION
+                        );
+                        let response = ServerMessage::Join(JoinResponse::Rejected {
+                            reason: format!(
+                                "server speaks protocol version {}, client speaks {}",
+                                PROTOCOL_VERSION, join.version
+                            ),
+                        });
+                        send(&socket, &response, client_addr).await;
+                        continue;
+                    }
+                    let player_name: String =
+                        join.name.trim().chars().take(MAX_NAME_LENGTH).collect();
+
+                    // A repeated join from a known address just gets its answer again
+                    let resumed = match clients.remove(&client_addr) {
+                        Some(client) => Some(client),
+                        None => join.session.and_then(|token| {
+                            resume_session(
+                                token,
+                                &mut clients,
+                                &mut departed,
+                                &mut game_state,
+                                &spawns,
+                            )
+                        }),
+                    };
+                    let mut client = match resumed {
+                        Some(client) => client,
+                        None => {
+                            let id = next_player_id;
+                            let player = Player::new(Position::default(), id, player_name.clone());
+                            if game_state.spawn_player(player, &spawns).is_err() {
+                                println!(
+                                    "Rejected {} from {}: no room to spawn",
+                                    player_name, client_addr
+                                );
+                                let response = ServerMessage::Join(JoinResponse::Rejected {
+                                    reason: String::from("the game is full"),
+                                });
+                                send(&socket, &response, client_addr).await;
+                                continue;
+                            }
+                            next_player_id += 1;
+                            println!(
+                                "New player connected with ID: {}, name: {}",
+                                id, player_name
+                            );
+                            Client::new(id, random(), game_state.tick)
+                        }
+                    };
+                    client.last_seen_tick = game_state.tick;
+
+                    let response = ServerMessage::Join(JoinResponse::Accepted {
+                        id: client.player_id,
+                        session: client.session,
+                        tick_rate,
+                        move_speed: config.move_speed,
+                    });
+                    send(&socket, &response, client_addr).await;
+                    clients.insert(client_addr, client);
+                }
+                ClientMessage::Update(update) => {
+                    // The sender's address decides who acts, the id in the packet must agree
+                    let Some(client) = clients.get_mut(&client_addr) else {
+                        continue;
+                    };
+                    if update.id != client.player_id {
+                        let reason = format!(
+                            "update for player {} from player {}",
+                            update.id, client.player_id
+                        );
+                        reject_packet(&mut rejected_packets, client_addr, &reason, game_state.tick);
+                    } else {
+                        match update.action {
+                            Action::Ping => {}
+                            Action::Look { angle, vertical }
+                                if !angle.is_finite() || !vertical.is_finite() =>
+                            {
+                                let reason = format!("look at {} {}", angle, vertical);
+                                reject_packet(
+                                    &mut rejected_packets,
+                                    client_addr,
+                                    &reason,
+                                    game_state.tick,
+                                );
+                            }
+                            Action::Move { angle, .. } | Action::Shoot { angle }
+                                if !angle.is_finite() =>
+                            {
+                                let reason = format!("{:?}", update.action);
+                                reject_packet(
+                                    &mut rejected_packets,
+                                    client_addr,
+                                    &reason,
+                                    game_state.tick,
+                                );
+                            }
+                            // Turning doesn't wait for the tick, moves and shots carry their own angle
+                            Action::Look { angle, vertical } => {
+                                let id = client.player_id;
+                                if let Some(player) =
+                                    game_state.players.iter_mut().find(|p| p.id == id)
+                                {
+                                    player.look(angle, vertical);
+                                }
+                            }
+                            // Queue moves and shots, they are made on the next tick
+                            Action::Move {
+                                sequence,
+                                keys,
+                                angle,
+                            } => client.queue_move(QueuedMove {
+                                sequence,
+                                keys,
+                                angle,
+                            }),
+                            Action::Shoot { angle } => client.queue_shot(angle),
+                        }
+                    }
+                }
+                ClientMessage::Ack { sequence } => {
+                    if let Some(client) = clients.get_mut(&client_addr) {
+                        client.acknowledge(sequence);
+                    }
+                }
+                ClientMessage::Disconnect => {
+                    if let Some(client) = clients.remove(&client_addr) {
+                        remove_client(client, LeaveReason::Quit, &mut game_state, &mut departed);
+                    }
                 }
             }
         }
 
-        let mut has_a_player_moved = false;
+        // Drop clients that went quiet without saying goodbye
+        let timeout_ticks = CLIENT_TIMEOUT * tick_rate;
+        let timed_out: Vec<SocketAddr> = clients
+            .iter()
+            .filter(|(_, client)| game_state.tick - client.last_seen_tick > timeout_ticks)
+            .map(|(&addr, _)| addr)
+            .collect();
+        for addr in timed_out {
+            if let Some(client) = clients.remove(&addr) {
+                remove_client(
+                    client,
+                    LeaveReason::TimedOut,
+                    &mut game_state,
+                    &mut departed,
+                );
+            }
+        }
+        let grace_ticks = SESSION_GRACE_PERIOD * tick_rate;
+        let tick = game_state.tick;
+        departed.retain(|_, session| tick - session.left_at_tick < grace_ticks);
+        let rejection_ticks = REJECTION_MEMORY * tick_rate;
+        rejected_packets.retain(|_, sender| tick - sender.last_tick < rejection_ticks);
+        let event_ticks = EVENT_LIFETIME * tick_rate;
+        game_state
+            .events
+            .retain(|event| tick - event.tick < event_ticks);
 
-        // Collect IDs of players that need to be repositioned
-        let mut reposition_player_ids = Vec::new();
+        // Moves and shots only count while a round is being played, moves made
+        // in between are still acknowledged so clients stop predicting them
+        let playing = round_state.is_playing();
 
-        for player in game_state.players.iter_mut() {
-            if let Some(idx) = &player.input(&mut game_state.maze, &mut has_a_player_moved) {
-                let x = idx % MAZE_WIDTH as u32;
-                let y = idx / MAZE_HEIGHT as u32;
+        let walk_distance = config.move_speed * TILE_SIZE / tick_rate as f32;
+        // Players that were shot, with who shot them, to respawn after the inputs
+        let hits = play_inputs(
+            clients.values_mut(),
+            &mut game_state,
+            playing,
+            walk_distance,
+            config.shot_range,
+        );
 
-                // Instead of another mutable borrow here, just collect the IDs
-                reposition_player_ids.push((player.id, x, y));
+        for (shooter, victim) in hits {
+            game_state.record_kill(shooter, victim, Weapon::Blaster);
+            let Some(index) = game_state.players.iter().position(|p| p.id == victim) else {
+                continue;
+            };
+            // the victim's own tile is free at worst
+            if let Some(tile) = game_state.spawn_tile(&spawns, Some(victim)) {
+                game_state.players[index].pos = tile_center(tile);
             }
         }
-
-        // Apply updates based on collected IDs, avoiding double mutable borrow
-        for (_player_id, x, y) in reposition_player_ids {
-            let mut rng = rand::thread_rng();
-            let new_pos: Position;
-            loop {
-                let new_x_tile = rng.gen_range(0..MAZE_WIDTH);
-                let new_y_tile = rng.gen_range(0..MAZE_HEIGHT);
-                let idx = new_y_tile * MAZE_WIDTH + new_x_tile;
-                if game_state.maze[idx] == EMPTY {
-                    // Calculate the center of the tile for the new position
-                    new_pos = Position {
-                        x: new_x_tile as f32 * TILE_SIZE + TILE_SIZE / 2.0,
-                        y: new_y_tile as f32 * TILE_SIZE + TILE_SIZE / 2.0,
+        match &round_state {
+            RoundState::Playing => {
+                //if one of the players has reached the score limit, start the intermission
+                if let Some(winner) = game_state.round_winner(config.score_limit) {
+                    println!("{} won round {}", winner, game_state.round);
+                    round_state = RoundState::Intermission {
+                        winner,
+                        ends_at_tick: game_state.tick + config.intermission_seconds * tick_rate,
                     };
-                    break;
                 }
             }
+            RoundState::Intermission { ends_at_tick, .. } if game_state.tick >= *ends_at_tick => {
+                game_state.round += 1;
+                if config
+                    .rounds
+                    .is_some_and(|rounds| game_state.round > rounds)
+                {
+                    game_state.round = 1;
+                }
+                (game_state.maze, game_state.seed, spawns) =
+                    round_maze(&config, &maps, game_state.round);
+                for player in game_state.players.iter_mut() {
+                    player.score = 0;
+                }
+                game_state.randomize_player_position(&spawns);
+                round_state = RoundState::Playing;
+            }
+            RoundState::Intermission { .. } => {}
+        }
+        game_state.phase = round_state.phase(game_state.tick, tick_rate);
 
-            // Find and reposition the player including the circle on the map
+        for client in clients.values() {
             if let Some(player) = game_state
                 .players
                 .iter_mut()
-                .find(|p| (p.pos.x / TILE_SIZE) as u32 == x && (p.pos.y / TILE_SIZE) as u32 == y)
+                .find(|p| p.id == client.player_id)
             {
-                player.pos = new_pos;
-                // use the player.pos to place a tile 1 on the map
-                let new_x = player.pos.x / TILE_SIZE;
-                let new_y = player.pos.y / TILE_SIZE;
-                let map_x = new_x as usize;
-                let map_y = new_y as usize;
-                let map_index = map_y * MAZE_WIDTH + map_x;
-                game_state.maze[map_index] = PLAYER;
-                has_a_player_moved = true;
+                player.ping = client.ping;
             }
         }
-        let new_round = game_state.update_level();
-        //if one of the players has reached the score limit (5), start a new round
 
-        if new_round {
-            if game_state.round >= 4 {
-                game_state.round = 1;
-            }
-            game_state.maze = select_maze(game_state.round);
-            for player in game_state.players.iter_mut() {
-                player.score = 0;
-                game_state.new_round_state = true;
-            }
-            game_state.randomize_player_position();
+        //broadcast the game state to all clients, stamped with this tick
+        history.push(game_state.tick, game_state.clone());
+        for (&addr, client) in clients.iter_mut() {
+            let message = client.state_message(&history, game_state.tick, &game_state);
+            send(&socket, &message, addr).await;
+            client.sent(game_state.tick);
         }
+    }
+}
+
+/// Fires each player's shots, then makes as many of their queued moves, in
+/// the order they arrived, as they have credit for. A player shot earlier in
+/// the tick is down for the rest of it: their shots are dropped and their
+/// moves only acknowledged. Returns who shot whom, to respawn afterwards.
+fn play_inputs<'a>(
+    clients: impl IntoIterator<Item = &'a mut Client>,
+    game_state: &mut GameState,
+    playing: bool,
+    walk_distance: f32,
+    shot_range: u32,
+) -> Vec<(PlayerId, PlayerId)> {
+    let mut hits = Vec::new();
+    let mut down = HashSet::new();
+    let mut occupancy = Occupancy::new(&game_state.players);
 
-        if has_a_player_moved || send_initial_gs {
-            //broadcast the game state to all clients
-            let broadcast_msg = serde_json::to_string(&game_state).unwrap();
-            for &addr in clients.keys() {
-                socket
-                    .send_to(broadcast_msg.as_bytes(), addr)
-                    .await
-                    .unwrap();
+    for client in clients {
+        let Some(player) = game_state
+            .players
+            .iter_mut()
+            .find(|p| p.id == client.player_id)
+        else {
+            continue;
+        };
+        for angle in client.shots.drain(..) {
+            if !playing || down.contains(&player.id) {
+                continue;
+            }
+            let maze = &mut game_state.maze;
+            if let Some(victim) = player.shoot(angle, maze, &mut occupancy, shot_range) {
+                hits.push((player.id, victim));
+                down.insert(victim);
             }
-            const DURATION_BETWEEN_LEVELS: u64 = 5;
-            if new_round {
-                tokio::time::sleep(std::time::Duration::from_secs(DURATION_BETWEEN_LEVELS)).await;
+        }
+        client.add_move_credit();
+        while let Some(queued) = client.next_move() {
+            if playing && !down.contains(&player.id) {
+                let maze = &game_state.maze;
+                player.walk(
+                    queued.keys,
+                    queued.angle,
+                    walk_distance,
+                    maze,
+                    &mut occupancy,
+                );
             }
-            game_state.new_round_state = false;
-            game_state.winner = String::from("");
+            player.input_sequence = queued.sequence;
         }
+        client.drop_move_backlog();
     }
+    hits
 }
 
-impl GameState {
-    fn update_level(&mut self) -> bool {
-        for player in self.players.iter() {
-            if player.score >= 5 {
-                self.round += 1;
-                self.winner = player.name.clone();
-                return true;
-            }
+/// Sends `message` to `addr`. A message too big for a datagram or one the
+/// network refuses, e.g. for an address nothing can be sent to, is dropped;
+/// a client that never hears from us times out.
+async fn send(socket: &UdpSocket, message: &ServerMessage, addr: SocketAddr) {
+    let sent = match encode(message) {
+        Ok(bytes) => socket.send_to(&bytes, addr).await.map(|_| ()),
+        Err(e) => Err(std::io::Error::other(e)),
+    };
+    if let Err(e) = sent {
+        println!("Failed to send to {}: {}", addr, e);
+    }
+}
+
+/// Loads the maps the playlist names from the configured directory.
+fn load_playlist_maps(config: &Config) -> Result<HashMap<String, DesignedMap>, String> {
+    let wanted = config.playlist.iter().filter_map(|entry| match entry {
+        PlaylistEntry::Map(name) => Some(name.as_str()),
+        PlaylistEntry::Generated(_) => None,
+    });
+    let maps = load_maps(&config.maps_dir, wanted)?;
+    if !maps.is_empty() {
+        println!(
+            "Loaded {} maps from {}",
+            maps.len(),
+            config.maps_dir.display()
+        );
+    }
+    Ok(maps)
+}
+
+/// The maze for `round` and the tiles players should spawn on, if it has
+/// any. Generated mazes come from the seed the config forces for the round
+/// or a random one, which is logged so the maze can be played again.
+fn round_maze(
+    config: &Config,
+    maps: &HashMap<String, DesignedMap>,
+    round: usize,
+) -> (Maze, Option<MazeSeed>, Vec<Tile>) {
+    match config.playlist_entry(round) {
+        PlaylistEntry::Map(name) => {
+            let map = &maps[name];
+            println!("Round {} map: {} ({})", round, name, map.stats);
+            (map.maze.clone(), None, map.spawns.clone())
         }
-        false
+        &PlaylistEntry::Generated(generator) => {
+            let seed = config.seeds.get(&round).copied().unwrap_or_else(random);
+            let level = config
+                .difficulty
+                .level(round, config.maze_width, config.maze_height);
+            println!(
+                "Round {} maze: {}x{} {}, seed {}",
+                round, level.width, level.height, generator, seed
+            );
+            let (maze, stats) = select_maze(generator, &level, seed);
+            println!("Round {} maze: {}", round, stats);
+            (maze, Some(seed), Vec::new())
+        }
+    }
+}
+
+/// Counts a refused packet against `addr`, logging the first one and then
+/// every hundredth so a flood doesn't drown the log.
+fn reject_packet(
+    rejected_packets: &mut HashMap<SocketAddr, RejectedSender>,
+    addr: SocketAddr,
+    reason: &str,
+    tick: u32,
+) {
+    if rejected_packets.len() >= MAX_REJECTED_SENDERS && !rejected_packets.contains_key(&addr) {
+        return;
+    }
+    let sender = rejected_packets.entry(addr).or_insert(RejectedSender {
+        count: 0,
+        last_tick: tick,
+    });
+    sender.count += 1;
+    sender.last_tick = tick;
+    if sender.count == 1 || sender.count.is_multiple_of(100) {
+        println!(
+            "Rejected packet from {}: {} ({} rejected so far)",
+            addr, reason, sender.count
+        );
+    }
+}
+
+/// Hands the session `token` to a new address: either a client still
+/// connected from somewhere else or a player who left within the grace period.
+fn resume_session(
+    token: SessionToken,
+    clients: &mut HashMap<SocketAddr, Client>,
+    departed: &mut HashMap<SessionToken, DepartedSession>,
+    game_state: &mut GameState,
+    spawns: &[Tile],
+) -> Option<Client> {
+    let old_addr = clients
+        .iter()
+        .find(|(_, client)| client.session == token)
+        .map(|(&addr, _)| addr);
+    if let Some(old_addr) = old_addr {
+        let mut client = clients.remove(&old_addr)?;
+        println!("Player {} moved from {}", client.player_id, old_addr);
+        // the new address may be a fresh process that never saw our states
+        client.reset_acknowledgements();
+        return Some(client);
+    }
+
+    let session = departed.remove(&token)?;
+    let mut player = session.player;
+    if session.round != game_state.round {
+        player.score = 0;
+    }
+    let (id, name) = (player.id, player.name.clone());
+    if let Err(player) = game_state.spawn_player(player, spawns) {
+        // keep the slot for when there is room again
+        println!("Player {} ({}) can't reconnect: no room", id, name);
+        departed.insert(token, DepartedSession { player, ..session });
+        return None;
+    }
+    println!("Player {} ({}) reconnected", id, name);
+    Some(Client::new(id, token, game_state.tick))
+}
+
+fn remove_client(
+    client: Client,
+    reason: LeaveReason,
+    game_state: &mut GameState,
+    departed: &mut HashMap<SessionToken, DepartedSession>,
+) {
+    if let Some(player) = game_state.player_left(client.player_id, reason) {
+        let session = DepartedSession {
+            player,
+            round: game_state.round,
+            left_at_tick: game_state.tick,
+        };
+        departed.insert(client.session, session);
+    }
+}
+
+/// The tile a position lies on.
+fn tile_of(pos: &Position) -> Tile {
+    ((pos.x / TILE_SIZE) as usize, (pos.y / TILE_SIZE) as usize)
+}
+
+/// The position in the middle of a tile.
+fn tile_center((x, y): Tile) -> Position {
+    Position {
+        x: x as f32 * TILE_SIZE + TILE_SIZE / 2.0,
+        y: y as f32 * TILE_SIZE + TILE_SIZE / 2.0,
+    }
+}
+
+trait Round {
+    fn round_winner(&self, score_limit: u32) -> Option<String>;
+    fn randomize_player_position(&mut self, spawns: &[Tile]);
+    fn spawn_tile(&self, spawns: &[Tile], except: Option<PlayerId>) -> Option<Tile>;
+    fn spawn_player(&mut self, player: Player, spawns: &[Tile]) -> Result<(), Player>;
+    fn player_left(&mut self, id: PlayerId, reason: LeaveReason) -> Option<Player>;
+    fn record_kill(&mut self, killer: PlayerId, victim: PlayerId, weapon: Weapon);
+    fn add_event(&mut self, kind: EventKind);
+}
+
+impl Round for GameState {
+    /// The safest free tile to put a player on, away from every player but
+    /// `except`. `None` when the maze is full.
+    fn spawn_tile(&self, spawns: &[Tile], except: Option<PlayerId>) -> Option<Tile> {
+        let others: Vec<Tile> = self
+            .players
+            .iter()
+            .filter(|p| Some(p.id) != except)
+            .map(|p| tile_of(&p.pos))
+            .collect();
+        choose_spawn(&self.maze, spawns, &others)
+    }
+
+    /// Puts a player in the game, or hands them back if the game is full
+    /// or there is no room.
+    fn spawn_player(&mut self, mut player: Player, spawns: &[Tile]) -> Result<(), Player> {
+        if self.players.len() >= MAX_PLAYERS {
+            return Err(player);
+        }
+        let Some(tile) = self.spawn_tile(spawns, None) else {
+            return Err(player);
+        };
+        player.pos = tile_center(tile);
+        self.players.push(player);
+        Ok(())
     }
-    fn randomize_player_position(&mut self) {
-        let mut rng = thread_rng();
+
+    fn player_left(&mut self, id: PlayerId, reason: LeaveReason) -> Option<Player> {
+        let index = self.players.iter().position(|p| p.id == id)?;
+        let player = self.players.remove(index);
+        println!("Player {} ({}) left: {:?}", player.id, player.name, reason);
+
+        self.add_event(EventKind::PlayerLeft {
+            id,
+            name: player.name.clone(),
+            reason,
+        });
+        Some(player)
+    }
+    /// Credits the killer, counts the victim's death and puts the kill in
+    /// the feed.
+    fn record_kill(&mut self, killer: PlayerId, victim: PlayerId, weapon: Weapon) {
+        let mut names = (String::new(), String::new());
         for player in self.players.iter_mut() {
-            let new_pos: Position;
-            loop {
-                let new_x_tile = rng.gen_range(0..MAZE_WIDTH);
-                let new_y_tile = rng.gen_range(0..MAZE_HEIGHT);
-                let idx = new_y_tile * MAZE_WIDTH + new_x_tile;
-                // Ensure the chosen position is empty
-                if self.maze[idx] == EMPTY {
-                    // Calculate the center of the tile for the new position
-                    new_pos = Position {
-                        x: new_x_tile as f32 * TILE_SIZE + TILE_SIZE / 2.0,
-                        y: new_y_tile as f32 * TILE_SIZE + TILE_SIZE / 2.0,
-                    };
+            if player.id == killer {
+                player.score += 1;
+                player.kills += 1;
+                player.streak += 1;
+                player.best_streak = player.best_streak.max(player.streak);
+                names.0 = player.name.clone();
+            } else if player.id == victim {
+                player.deaths += 1;
+                player.streak = 0;
+                names.1 = player.name.clone();
+            }
+        }
+        let (killer_name, victim_name) = names;
+        println!("{} killed {} with {:?}", killer_name, victim_name, weapon);
 
-                    break;
+        self.add_event(EventKind::Kill {
+            killer,
+            killer_name,
+            victim,
+            victim_name,
+            weapon,
+        });
+    }
+    /// Puts an event in the feed, making room by dropping the oldest.
+    fn add_event(&mut self, kind: EventKind) {
+        if self.events.len() == MAX_EVENTS {
+            self.events.remove(0);
+        }
+        self.events.push(GameEvent {
+            tick: self.tick,
+            kind,
+        });
+    }
+    fn round_winner(&self, score_limit: u32) -> Option<String> {
+        self.players
+            .iter()
+            .find(|player| player.score >= score_limit)
+            .map(|player| player.name.clone())
+    }
+    fn randomize_player_position(&mut self, spawns: &[Tile]) {
+        // the maze is new, so only players placed so far count
+        let mut placed: Vec<Tile> = Vec::with_capacity(self.players.len());
+        for index in 0..self.players.len() {
+            let tile = choose_spawn(&self.maze, spawns, &placed).or_else(|| {
+                // more players than floor: doubling up beats leaving one out
+                println!("No free tile for {}", self.players[index].name);
+                placed.choose(&mut thread_rng()).copied()
+            });
+            let Some(tile) = tile else {
+                continue;
+            };
+            self.players[index].pos = tile_center(tile);
+            placed.push(tile);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maze_wars_protocol::testing::{at, maze};
+    use maze_wars_protocol::MoveKeys;
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    const VICTIM: PlayerId = 1;
+
+    /// Player 1 at the corner of an L, walking away from it and aiming down
+    /// at player 2, while players 0 and 2 aim at them along the two arms.
+    /// Their clients come in `order`.
+    fn crossfire(order: [PlayerId; 3]) -> (GameState, Vec<Client>) {
+        let corner = maze(&[
+            "#######", "#.....#", "#####.#", "#####.#", "#####.#", "#######",
+        ]);
+        let mut game_state = new_game_state(corner, None);
+        game_state.players = [at(1.5, 1.5), at(5.5, 1.5), at(5.5, 4.5)]
+            .into_iter()
+            .zip(0..)
+            .map(|(pos, id)| Player::new(pos, id, format!("player{id}")))
+            .collect();
+        let clients = order
+            .into_iter()
+            .map(|id| {
+                let mut client = Client::new(id, 0, 0);
+                match id {
+                    0 => client.queue_shot(0.0),
+                    VICTIM => {
+                        client.queue_move(QueuedMove {
+                            sequence: 7,
+                            keys: MoveKeys {
+                                forward: true,
+                                ..MoveKeys::default()
+                            },
+                            angle: PI,
+                        });
+                        client.queue_shot(FRAC_PI_2);
+                    }
+                    _ => client.queue_shot(-FRAC_PI_2),
                 }
+                client
+            })
+            .collect();
+        (game_state, clients)
+    }
+
+    #[test]
+    fn a_player_shot_twice_in_one_tick_dies_once() {
+        let orders = [
+            [0, 1, 2],
+            [0, 2, 1],
+            [1, 0, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [2, 1, 0],
+        ];
+        for order in orders {
+            let (mut game_state, mut clients) = crossfire(order);
+            let hits = play_inputs(clients.iter_mut(), &mut game_state, true, TILE_SIZE, 20);
+
+            let victim = &game_state.players[VICTIM as usize];
+            assert_eq!(victim.input_sequence, 7, "{:?}", order);
+            if order[0] == VICTIM {
+                // they walked and shot before anyone got them
+                assert_eq!(hits, [(VICTIM, 2), (0, VICTIM)], "{:?}", order);
+                assert!(victim.pos.x < at(5.0, 1.5).x, "{:?}", order);
+            } else {
+                // the first shooter gets them, and they neither move nor shoot
+                assert_eq!(hits, [(order[0], VICTIM)], "{:?}", order);
+                assert_eq!(victim.pos, at(5.5, 1.5), "{:?}", order);
             }
-            player.pos = new_pos;
-            let new_x = player.pos.x / TILE_SIZE;
-            let new_y = player.pos.y / TILE_SIZE;
-            let map_x = new_x as usize;
-            let map_y = new_y as usize;
-            let map_index = map_y * MAZE_WIDTH + map_x;
-            self.maze[map_index] = PLAYER;
+            assert!(clients.iter().all(|client| client.shots.is_empty()));
+        }
+    }
+
+    #[test]
+    fn refused_senders_are_capped_but_known_ones_still_counted() {
+        let mut rejected = HashMap::new();
+        let addr = |port| SocketAddr::from(([127, 0, 0, 1], port));
+        for port in 0..MAX_REJECTED_SENDERS as u16 + 10 {
+            reject_packet(&mut rejected, addr(port), "junk", 1);
         }
+        assert_eq!(rejected.len(), MAX_REJECTED_SENDERS);
+        assert!(!rejected.contains_key(&addr(MAX_REJECTED_SENDERS as u16)));
+
+        reject_packet(&mut rejected, addr(0), "junk", 5);
+        assert_eq!(rejected[&addr(0)].count, 2);
+        assert_eq!(rejected[&addr(0)].last_tick, 5);
     }
 }
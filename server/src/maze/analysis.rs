@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use maze_wars_protocol::EMPTY;
+
+use super::Grid;
+
+/// Shortest path lengths are averaged over walks from this many floor
+/// tiles, spread evenly over the maze, rather than from every one of them.
+const PATH_SAMPLES: usize = 16;
+
+/// Smallest share of the maze that has to be floor.
+const MIN_FLOOR_SHARE: f32 = 0.2;
+
+/// Largest share of the floor that may be dead ends.
+const MAX_DEAD_END_SHARE: f32 = 0.2;
+
+/// Fewest loops a maze needs so there is always a way around an opponent.
+const MIN_LOOPS: usize = 1;
+
+/// How walkable a maze is, counted over its empty tiles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MazeStats {
+    pub floor: usize,
+    /// Floor tiles that can be walked to from the first one.
+    pub reachable: usize,
+    /// Floor tiles with only one way out.
+    pub dead_ends: usize,
+    /// Independent cycles in the floor, each one a way to circle around.
+    pub loops: usize,
+    /// Average number of steps between two floor tiles.
+    pub average_path: f32,
+}
+
+impl MazeStats {
+    pub fn analyse(grid: &Grid) -> Self {
+        let height = grid.len();
+        let width = grid[0].len();
+        let floor_tiles: Vec<(usize, usize)> = (0..height)
+            .flat_map(|row| (0..width).map(move |col| (row, col)))
+            .filter(|&(row, col)| grid[row][col] == EMPTY)
+            .collect();
+
+        let mut dead_ends = 0;
+        let mut edges = 0;
+        for &(row, col) in &floor_tiles {
+            let exits = floor_neighbours(grid, (row, col)).count();
+            if exits == 1 {
+                dead_ends += 1;
+            }
+            edges += exits;
+        }
+        // every edge was counted from both of its ends
+        let edges = edges / 2;
+
+        let mut components = 0;
+        let mut reachable = 0;
+        let mut seen = vec![vec![false; width]; height];
+        for &start in &floor_tiles {
+            if seen[start.0][start.1] {
+                continue;
+            }
+            let distances = walk(grid, start);
+            let size = distances.iter().flatten().flatten().count();
+            for (row, tiles) in distances.iter().enumerate() {
+                for (col, distance) in tiles.iter().enumerate() {
+                    seen[row][col] |= distance.is_some();
+                }
+            }
+            if components == 0 {
+                reachable = size;
+            }
+            components += 1;
+        }
+
+        let step = (floor_tiles.len() / PATH_SAMPLES).max(1);
+        let (mut total, mut paths) = (0, 0);
+        for &start in floor_tiles.iter().step_by(step) {
+            for distance in walk(grid, start).into_iter().flatten().flatten() {
+                total += distance;
+                paths += 1;
+            }
+        }
+
+        MazeStats {
+            floor: floor_tiles.len(),
+            reachable,
+            dead_ends,
+            loops: (edges + components).saturating_sub(floor_tiles.len()),
+            average_path: if paths == 0 {
+                0.0
+            } else {
+                total as f32 / paths as f32
+            },
+        }
+    }
+
+    /// Why a maze isn't good enough to play on, if it isn't.
+    pub fn problem(&self, width: usize, height: usize) -> Option<String> {
+        if self.reachable < self.floor {
+            Some(format!(
+                "{} of {} floor tiles are cut off",
+                self.floor - self.reachable,
+                self.floor
+            ))
+        } else if (self.floor as f32) < (width * height) as f32 * MIN_FLOOR_SHARE {
+            Some(format!("only {} floor tiles", self.floor))
+        } else if self.dead_ends as f32 > self.floor as f32 * MAX_DEAD_END_SHARE {
+            Some(format!("{} dead ends", self.dead_ends))
+        } else if self.loops < MIN_LOOPS {
+            Some(format!("{} loops", self.loops))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for MazeStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} floor tiles, {} dead ends, {} loops, average path {:.1}",
+            self.floor, self.dead_ends, self.loops, self.average_path
+        )
+    }
+}
+
+fn floor_neighbours(
+    grid: &Grid,
+    (row, col): (usize, usize),
+) -> impl Iterator<Item = (usize, usize)> + '_ {
+    [(0, 1), (0, -1), (1, 0), (-1, 0)]
+        .into_iter()
+        .map(move |(dy, dx)| ((row as i32 + dy) as usize, (col as i32 + dx) as usize))
+        .filter(|&(row, col)| {
+            grid.get(row)
+                .and_then(|tiles| tiles.get(col))
+                .is_some_and(|&tile| tile == EMPTY)
+        })
+}
+
+/// Steps from `start` to every floor tile it is connected to.
+fn walk(grid: &Grid, start: (usize, usize)) -> Vec<Vec<Option<usize>>> {
+    let mut distances = vec![vec![None; grid[0].len()]; grid.len()];
+    distances[start.0][start.1] = Some(0);
+    let mut queue = VecDeque::from([(start, 0)]);
+    while let Some((tile, distance)) = queue.pop_front() {
+        for (row, col) in floor_neighbours(grid, tile) {
+            if distances[row][col].is_none() {
+                distances[row][col] = Some(distance + 1);
+                queue.push_back(((row, col), distance + 1));
+            }
+        }
+    }
+    distances
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maze_wars_protocol::testing::maze;
+
+    fn grid(rows: &[&str]) -> Grid {
+        let maze = maze(rows);
+        maze.tiles.chunks(maze.width).map(<[u8]>::to_vec).collect()
+    }
+
+    #[test]
+    fn a_ring_is_one_loop_without_dead_ends() {
+        let stats = MazeStats::analyse(&grid(&["#####", "#...#", "#.#.#", "#...#", "#####"]));
+        assert_eq!(stats.floor, 8);
+        assert_eq!(stats.reachable, 8);
+        assert_eq!(stats.dead_ends, 0);
+        assert_eq!(stats.loops, 1);
+    }
+
+    #[test]
+    fn an_open_square_is_one_loop() {
+        let stats = MazeStats::analyse(&grid(&["####", "#..#", "#..#", "####"]));
+        assert_eq!((stats.floor, stats.dead_ends, stats.loops), (4, 0, 1));
+    }
+
+    #[test]
+    fn a_corridor_has_two_dead_ends_and_no_loop() {
+        let stats = MazeStats::analyse(&grid(&["#####", "#...#", "#####"]));
+        assert_eq!((stats.floor, stats.dead_ends, stats.loops), (3, 2, 0));
+        // from every tile to every tile, itself included: 8 steps over 9 paths
+        assert!((stats.average_path - 8.0 / 9.0).abs() < 1e-6);
+        assert_eq!(stats.problem(5, 3), Some(String::from("2 dead ends")));
+    }
+
+    #[test]
+    fn cut_off_floor_is_not_reachable() {
+        let stats = MazeStats::analyse(&grid(&["######", "#..#.#", "######"]));
+        assert_eq!(stats.floor, 3);
+        assert_eq!(stats.reachable, 2);
+        assert_eq!(stats.dead_ends, 2);
+        assert_eq!(stats.loops, 0);
+        assert_eq!(
+            stats.problem(6, 3),
+            Some(String::from("1 of 3 floor tiles are cut off"))
+        );
+    }
+
+    #[test]
+    fn too_little_floor_is_a_problem() {
+        let mut rows = vec!["##########"; 10];
+        rows[1] = "#..#######";
+        rows[2] = "#..#######";
+        let stats = MazeStats::analyse(&grid(&rows));
+        assert_eq!(
+            stats.problem(10, 10),
+            Some(String::from("only 4 floor tiles"))
+        );
+    }
+}
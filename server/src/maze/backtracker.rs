@@ -0,0 +1,44 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use super::{
+    carve_passage, cell_count, cell_neighbours, loop_count, open_loops, walled, Grid, Level,
+    MazeGenerator,
+};
+
+/// Randomized depth-first search: long winding passages with few branches.
+pub struct Backtracker;
+
+impl MazeGenerator for Backtracker {
+    fn generate(&self, width: usize, height: usize, level: &Level, rng: &mut StdRng) -> Grid {
+        let mut maze = walled(width, height);
+        let cells = cell_count(width, height);
+        let mut visited = vec![vec![false; cells.0]; cells.1];
+
+        let start = (rng.gen_range(0..cells.0), rng.gen_range(0..cells.1));
+        visited[start.1][start.0] = true;
+        let mut stack = vec![start];
+
+        while let Some(&cell) = stack.last() {
+            let unvisited: Vec<_> = cell_neighbours(cell, cells)
+                .into_iter()
+                .filter(|&(x, y)| !visited[y][x])
+                .collect();
+            match unvisited.choose(rng) {
+                Some(&next) => {
+                    visited[next.1][next.0] = true;
+                    carve_passage(&mut maze, cell, next);
+                    stack.push(next);
+                }
+                // dead end, back up until there is somewhere new to go
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+
+        open_loops(&mut maze, loop_count(cells.0 * cells.1, level), rng);
+        maze
+    }
+}
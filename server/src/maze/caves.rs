@@ -0,0 +1,55 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use super::{Grid, Level, MazeGenerator};
+use maze_wars_protocol::{EMPTY, WALL};
+
+/// Share of the inside that starts out as wall on a level with no wall
+/// density, and how much more each unit of density adds: 0.45 at the
+/// default density of round 1.
+const INITIAL_WALLS: f64 = 0.4;
+const WALLS_PER_DENSITY: f64 = 0.4;
+
+const SMOOTHING_STEPS: usize = 4;
+
+/// Cellular automaton caves: random noise smoothed into open caverns.
+pub struct Caves;
+
+impl MazeGenerator for Caves {
+    fn generate(&self, width: usize, height: usize, level: &Level, rng: &mut StdRng) -> Grid {
+        let fill = INITIAL_WALLS + WALLS_PER_DENSITY * level.wall_density as f64;
+        let border = |x: usize, y: usize| x == 0 || y == 0 || x == width - 1 || y == height - 1;
+        let mut maze: Grid = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        if border(x, y) || rng.gen_bool(fill) {
+                            WALL
+                        } else {
+                            EMPTY
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for _ in 0..SMOOTHING_STEPS {
+            let previous = maze.clone();
+            for (y, row) in maze.iter_mut().enumerate().take(height - 1).skip(1) {
+                for (x, tile) in row.iter_mut().enumerate().take(width - 1).skip(1) {
+                    // a tile follows the majority of the eight around it
+                    let walls = (y - 1..=y + 1)
+                        .flat_map(|ny| (x - 1..=x + 1).map(move |nx| (nx, ny)))
+                        .filter(|&(nx, ny)| (nx, ny) != (x, y) && previous[ny][nx] != EMPTY)
+                        .count();
+                    if walls > 4 {
+                        *tile = WALL;
+                    } else if walls < 4 {
+                        *tile = EMPTY;
+                    }
+                }
+            }
+        }
+        maze
+    }
+}
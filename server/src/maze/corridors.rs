@@ -0,0 +1,109 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use super::{Grid, Level, MazeGenerator};
+use maze_wars_protocol::{BREAKABLE, EMPTY, WALL};
+
+fn adjacent_is(cell: u8, x: usize, y: usize, maze: &[Vec<u8>]) -> bool {
+    maze[y][x + 1] == cell && maze[y][x - 1] == cell
+}
+
+/// The striped template the corridors start from: corridors on
+/// every other row, joined at both ends, with a double-width corridor across
+/// the middle.
+fn generic_maze(width: usize, height: usize) -> Grid {
+    let middle = height / 2;
+    (0..height)
+        .map(|y| {
+            let wall_row = (y < middle - 1 && y % 2 == 0)
+                || (y > middle && y < height - 2 && (y - middle) % 2 == 1);
+            (0..width)
+                .map(|x| {
+                    let border = y == 0 || y == height - 1 || x == 0 || x == width - 1;
+                    let inner_wall = wall_row && x >= 2 && x < width - 2;
+                    if border || inner_wall {
+                        WALL
+                    } else {
+                        EMPTY
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The original layout: long horizontal corridors with random gaps knocked
+/// into the walls between them and bricks dropped into them. Open levels
+/// knock more gaps, dense ones drop more bricks.
+pub struct Corridors;
+
+impl MazeGenerator for Corridors {
+    fn generate(&self, width: usize, height: usize, level: &Level, rng: &mut StdRng) -> Grid {
+        let num_removed_bricks = (level.openness * (width / 5) as f32).round() as usize;
+        // bricks can't sit side by side, a third of the row always fits
+        let num_added_bricks =
+            ((level.wall_density * width as f32).round() as usize).min((width - 4) / 3);
+
+        // Start from the striped template
+        let mut maze = generic_maze(width, height);
+        let middle = height / 2;
+
+        // Remove x amount of random bricks from each wall
+        for row in (2..middle - 1).step_by(2) {
+            let mut removed_bricks = 0;
+            while removed_bricks < num_removed_bricks {
+                let random_index = rng.gen_range(2..width - 2);
+                if adjacent_is(WALL, random_index, row, &maze) {
+                    maze[row][random_index] = EMPTY;
+                    removed_bricks += 1;
+                }
+            }
+        }
+
+        // Remove x amount of random bricks from each wall
+        for row in (middle + 1..height - 2).step_by(2) {
+            let mut removed_bricks = 0;
+            while removed_bricks < num_removed_bricks {
+                let random_index = rng.gen_range(2..width - 2);
+                if adjacent_is(WALL, random_index, row, &maze) {
+                    maze[row][random_index] = EMPTY;
+                    removed_bricks += 1;
+                }
+            }
+        }
+
+        // Add x amount of bricks to each empty row
+        for i in (3..middle - 1).step_by(2) {
+            let mut added_bricks = 0;
+            while added_bricks < num_added_bricks {
+                let random_index = rng.gen_range(2..width - 2);
+                if adjacent_is(EMPTY, random_index, i, &maze) {
+                    maze[i][random_index] = WALL;
+                    // Add some random breakables
+                    if rng.gen_range(0..10) > 3 {
+                        maze[i][random_index] = BREAKABLE;
+                    }
+                    added_bricks += 1;
+                }
+            }
+        }
+
+        // Add x amount of bricks to each empty row
+        for i in (middle..height - 3).step_by(2) {
+            let mut added_bricks = 0;
+            while added_bricks < num_added_bricks {
+                let random_index = rng.gen_range(2..width - 2);
+                if adjacent_is(EMPTY, random_index, i, &maze) {
+                    maze[i][random_index] = WALL;
+                    // Add some random breakables
+                    if rng.gen_range(0..10) > 3 {
+                        maze[i][random_index] = BREAKABLE;
+                    }
+                    added_bricks += 1;
+                }
+            }
+        }
+
+        maze
+    }
+}
@@ -0,0 +1,219 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use serde::Deserialize;
+
+use super::{MAX_MAZE_SIZE, MIN_MAZE_SIZE};
+
+/// How one property of the mazes changes from round to round: its value in
+/// round 1, how much it moves each round after that, and where it stops.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Curve {
+    pub start: f32,
+    #[serde(default)]
+    pub step: f32,
+    pub limit: f32,
+}
+
+impl Curve {
+    /// Never changes.
+    const fn flat(value: f32) -> Self {
+        Curve {
+            start: value,
+            step: 0.0,
+            limit: value,
+        }
+    }
+
+    pub fn at(&self, round: usize) -> f32 {
+        let value = self.start + self.step * round.saturating_sub(1) as f32;
+        if self.step < 0.0 {
+            value.max(self.limit)
+        } else {
+            value.min(self.limit)
+        }
+    }
+
+    fn check(&self, name: &str, allowed: RangeInclusive<f32>) -> Result<(), String> {
+        for value in [self.start, self.limit] {
+            if !allowed.contains(&value) {
+                return Err(format!(
+                    "difficulty {} must stay between {} and {}, got {}",
+                    name,
+                    allowed.start(),
+                    allowed.end(),
+                    value
+                ));
+            }
+        }
+        if !self.step.is_finite() {
+            return Err(format!("difficulty {} step must be a number", name));
+        }
+        // a limit behind the start would be where every round is
+        let towards_limit = if self.step < 0.0 {
+            self.limit <= self.start
+        } else {
+            self.limit >= self.start
+        };
+        if !towards_limit {
+            return Err(format!(
+                "difficulty {} steps away from its limit: start {}, step {}, limit {}",
+                name, self.start, self.step, self.limit
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Curve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.step == 0.0 || self.start == self.limit {
+            write!(f, "{}", self.start)
+        } else {
+            write!(
+                f,
+                "{}, {:+} a round until {}",
+                self.start, self.step, self.limit
+            )
+        }
+    }
+}
+
+/// The difficulty curves of a server, each read off per round.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Difficulty {
+    /// How many extra ways through the walls are opened, from 0 (none) to 1.
+    pub openness: Curve,
+    /// How cluttered the open space gets, from 0 to 0.5: bricks in the
+    /// corridors, rock in the caves.
+    pub wall_density: Curve,
+    /// Share of the inner walls that can be shot away.
+    pub breakable_ratio: Curve,
+    /// Maze size, as a multiple of the configured width and height.
+    pub size: Curve,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty {
+            // fewer gaps every round, as the first four rounds always had
+            openness: Curve {
+                start: 0.75,
+                step: -0.25,
+                limit: 0.25,
+            },
+            wall_density: Curve {
+                start: 0.125,
+                step: 0.025,
+                limit: 0.25,
+            },
+            breakable_ratio: Curve {
+                start: 0.03,
+                step: -0.005,
+                limit: 0.01,
+            },
+            size: Curve::flat(1.0),
+        }
+    }
+}
+
+impl Difficulty {
+    pub fn validate(&self) -> Result<(), String> {
+        self.openness.check("openness", 0.0..=1.0)?;
+        self.wall_density.check("wall_density", 0.0..=0.5)?;
+        self.breakable_ratio.check("breakable_ratio", 0.0..=1.0)?;
+        self.size.check("size", 0.25..=8.0)
+    }
+
+    /// What the maze of `round` is made like, on a server whose mazes are
+    /// `width` x `height` to begin with.
+    pub fn level(&self, round: usize, width: usize, height: usize) -> Level {
+        let scale = |side: usize| {
+            ((side as f32 * self.size.at(round)).round() as usize)
+                .clamp(MIN_MAZE_SIZE, MAX_MAZE_SIZE)
+        };
+        Level {
+            width: scale(width),
+            height: scale(height),
+            openness: self.openness.at(round),
+            wall_density: self.wall_density.at(round),
+            breakable_ratio: self.breakable_ratio.at(round),
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "  openness: {}", self.openness)?;
+        writeln!(f, "  wall density: {}", self.wall_density)?;
+        writeln!(f, "  breakable ratio: {}", self.breakable_ratio)?;
+        write!(f, "  maze scale: {}", self.size)
+    }
+}
+
+/// The difficulty of one round's maze.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Level {
+    pub width: usize,
+    pub height: usize,
+    pub openness: f32,
+    pub wall_density: f32,
+    pub breakable_ratio: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve(start: f32, step: f32, limit: f32) -> Curve {
+        Curve { start, step, limit }
+    }
+
+    #[test]
+    fn curves_step_until_their_limit() {
+        let rising = curve(0.1, 0.05, 0.2);
+        assert_eq!(rising.at(1), 0.1);
+        assert!((rising.at(2) - 0.15).abs() < 1e-6);
+        assert_eq!(rising.at(9), 0.2);
+        let falling = curve(0.75, -0.25, 0.25);
+        assert_eq!(falling.at(2), 0.5);
+        assert_eq!(falling.at(9), 0.25);
+    }
+
+    #[test]
+    fn the_default_difficulty_is_valid() {
+        assert_eq!(Difficulty::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_a_step_away_from_the_limit() {
+        for bad in [
+            curve(0.25, 0.1, 0.1),
+            curve(0.1, -0.1, 0.25),
+            curve(0.25, 0.0, 0.1),
+        ] {
+            assert!(bad.check("openness", 0.0..=1.0).is_err(), "{:?}", bad);
+        }
+        for good in [
+            curve(0.1, 0.1, 0.25),
+            curve(0.25, -0.1, 0.1),
+            curve(0.5, 0.0, 0.5),
+            curve(0.1, 0.0, 0.25),
+        ] {
+            assert_eq!(good.check("openness", 0.0..=1.0), Ok(()));
+        }
+    }
+
+    #[test]
+    fn rejects_values_out_of_range() {
+        let error = curve(0.0, 0.1, 1.5).check("openness", 0.0..=1.0);
+        assert_eq!(
+            error,
+            Err(String::from(
+                "difficulty openness must stay between 0 and 1, got 1.5"
+            ))
+        );
+    }
+}
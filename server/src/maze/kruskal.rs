@@ -0,0 +1,72 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+use super::{
+    carve_passage, cell_count, loop_count, open_loops, walled, Grid, Level, MazeGenerator,
+};
+
+/// Randomized Kruskal's algorithm: walls are knocked down in random order
+/// unless that would join two cells that are already connected.
+pub struct Kruskal;
+
+impl MazeGenerator for Kruskal {
+    fn generate(&self, width: usize, height: usize, level: &Level, rng: &mut StdRng) -> Grid {
+        let mut maze = walled(width, height);
+        let cells = cell_count(width, height);
+
+        let mut walls = Vec::new();
+        for y in 0..cells.1 {
+            for x in 0..cells.0 {
+                if x + 1 < cells.0 {
+                    walls.push(((x, y), (x + 1, y)));
+                }
+                if y + 1 < cells.1 {
+                    walls.push(((x, y), (x, y + 1)));
+                }
+            }
+        }
+        walls.shuffle(rng);
+
+        // every cell starts in a set of its own
+        let mut sets = DisjointSets::new(cells.0 * cells.1);
+        for (a, b) in walls {
+            if sets.union(a.1 * cells.0 + a.0, b.1 * cells.0 + b.0) {
+                carve_passage(&mut maze, a, b);
+            }
+        }
+
+        open_loops(&mut maze, loop_count(cells.0 * cells.1, level), rng);
+        maze
+    }
+}
+
+struct DisjointSets {
+    parents: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+        }
+    }
+
+    fn root(&mut self, mut item: usize) -> usize {
+        while self.parents[item] != item {
+            // point halfway up to keep the paths short
+            self.parents[item] = self.parents[self.parents[item]];
+            item = self.parents[item];
+        }
+        item
+    }
+
+    /// Joins the sets holding `a` and `b`; false if they already were one.
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.root(a), self.root(b));
+        if a == b {
+            return false;
+        }
+        self.parents[a] = b;
+        true
+    }
+}
@@ -0,0 +1,274 @@
+//! Hand-made maps, drawn as text with one character per tile:
+//!
+//! ```text
+//! #  wall
+//! %  breakable wall
+//! .  empty floor
+//! S  empty floor players can spawn on
+//! ```
+//!
+//! Every row must be as long as the first, and the outer ring must be wall.
+
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::path::Path;
+
+use maze_wars_protocol::{Maze, BREAKABLE, EMPTY, WALL};
+
+use super::{flood_fill, MazeStats, MAX_MAZE_SIZE, MIN_MAZE_SIZE};
+use crate::spawn::Tile;
+
+/// Spawn points a map needs so a handful of players don't start on top of
+/// each other.
+const MIN_SPAWN_POINTS: usize = 4;
+
+#[derive(Debug, Clone)]
+pub struct DesignedMap {
+    pub maze: Maze,
+    /// Tiles players can spawn on, as (column, row).
+    pub spawns: Vec<Tile>,
+    pub stats: MazeStats,
+}
+
+/// Reads the maps called `names` from `dir`, each from its NAME.txt, keyed
+/// by name. Other files there are left alone.
+pub fn load_maps<'a>(
+    dir: &Path,
+    names: impl IntoIterator<Item = &'a str>,
+) -> Result<HashMap<String, DesignedMap>, String> {
+    let mut maps = HashMap::new();
+    for name in names {
+        if maps.contains_key(name) {
+            continue;
+        }
+        let path = dir.join(format!("{}.txt", name));
+        let text = std::fs::read_to_string(&path).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => format!("no map {:?} in {}", name, dir.display()),
+            _ => format!("can't read {}: {}", path.display(), e),
+        })?;
+        let map = parse_map(&text).map_err(|e| format!("bad map {}: {}", path.display(), e))?;
+        maps.insert(name.to_string(), map);
+    }
+    Ok(maps)
+}
+
+pub fn parse_map(text: &str) -> Result<DesignedMap, String> {
+    let rows: Vec<&str> = text.lines().map(str::trim_end).collect();
+    let rows = match rows.iter().rposition(|row| !row.is_empty()) {
+        Some(last) => &rows[..=last],
+        None => return Err(String::from("the map is empty")),
+    };
+
+    let height = rows.len();
+    let width = rows[0].chars().count();
+    for (name, size) in [("wide", width), ("high", height)] {
+        if !(MIN_MAZE_SIZE..=MAX_MAZE_SIZE).contains(&size) {
+            return Err(format!(
+                "the map is {} tiles {}, it must be between {} and {}",
+                size, name, MIN_MAZE_SIZE, MAX_MAZE_SIZE
+            ));
+        }
+    }
+
+    let mut grid = Vec::with_capacity(height);
+    let mut spawns = Vec::new();
+    for (y, row) in rows.iter().enumerate() {
+        if row.chars().count() != width {
+            return Err(format!(
+                "row {} is {} tiles long, the first is {}",
+                y + 1,
+                row.chars().count(),
+                width
+            ));
+        }
+        let tiles = row
+            .chars()
+            .enumerate()
+            .map(|(x, c)| match c {
+                '#' => Ok(WALL),
+                '%' => Ok(BREAKABLE),
+                '.' => Ok(EMPTY),
+                'S' => {
+                    spawns.push((x, y));
+                    Ok(EMPTY)
+                }
+                other => Err(format!(
+                    "unknown tile {:?} at row {}, column {}",
+                    other,
+                    y + 1,
+                    x + 1
+                )),
+            })
+            .collect::<Result<Vec<u8>, String>>()?;
+        grid.push(tiles);
+    }
+
+    let open_border = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| x == 0 || y == 0 || x == width - 1 || y == height - 1)
+        .find(|&(x, y)| grid[y][x] != WALL);
+    if let Some((x, y)) = open_border {
+        return Err(format!(
+            "the edge is open at row {}, column {}, it must be all wall",
+            y + 1,
+            x + 1
+        ));
+    }
+
+    if spawns.len() < MIN_SPAWN_POINTS {
+        return Err(format!(
+            "the map has {} spawn points, it needs at least {}",
+            spawns.len(),
+            MIN_SPAWN_POINTS
+        ));
+    }
+
+    // every floor tile must be walkable to from the first spawn point
+    let mut reachable = HashSet::new();
+    let (spawn_x, spawn_y) = spawns[0];
+    flood_fill(&grid, (spawn_y, spawn_x), &mut reachable);
+    let cut_off = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .find(|&(x, y)| grid[y][x] == EMPTY && !reachable.contains(&(y, x)));
+    if let Some((x, y)) = cut_off {
+        return Err(format!(
+            "the floor at row {}, column {} can't be reached from the spawn points",
+            y + 1,
+            x + 1
+        ));
+    }
+
+    let stats = MazeStats::analyse(&grid);
+    let tiles = grid.into_iter().flatten().collect();
+    Ok(DesignedMap {
+        maze: Maze::new(width, height, tiles),
+        spawns,
+        stats,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOM: &str = "\
+########
+#S....S#
+#..##..#
+#..%#..#
+#......#
+#..##..#
+#S....S#
+########
+";
+
+    fn error(text: &str) -> String {
+        parse_map(text).unwrap_err()
+    }
+
+    #[test]
+    fn parses_a_map() {
+        let map = parse_map(ROOM).unwrap();
+        assert_eq!((map.maze.width, map.maze.height), (8, 8));
+        assert_eq!(map.spawns, vec![(1, 1), (6, 1), (1, 6), (6, 6)]);
+        assert_eq!(map.maze.get(3, 3), Some(BREAKABLE));
+        assert_eq!(map.maze.get(3, 2), Some(WALL));
+        assert_eq!(map.maze.get(6, 1), Some(EMPTY));
+        assert_eq!(map.stats.reachable, map.stats.floor);
+    }
+
+    #[test]
+    fn crlf_and_trailing_blank_lines_are_fine() {
+        let crlf = ROOM.replace('\n', "\r\n") + "\r\n\r\n";
+        let map = parse_map(&crlf).unwrap();
+        assert_eq!(map.maze, parse_map(ROOM).unwrap().maze);
+    }
+
+    #[test]
+    fn rejects_an_open_edge() {
+        let open = ROOM.replacen("########", "##.#####", 1);
+        assert_eq!(
+            error(&open),
+            "the edge is open at row 1, column 3, it must be all wall"
+        );
+    }
+
+    #[test]
+    fn rejects_uneven_rows() {
+        let uneven = ROOM.replacen("#..##..#", "#..##.#", 1);
+        assert_eq!(error(&uneven), "row 3 is 7 tiles long, the first is 8");
+    }
+
+    #[test]
+    fn rejects_unknown_tiles() {
+        let unknown = ROOM.replacen("#..%#..#", "#..%#.x#", 1);
+        assert_eq!(error(&unknown), "unknown tile 'x' at row 4, column 7");
+    }
+
+    #[test]
+    fn rejects_too_few_spawn_points() {
+        let few = ROOM.replacen("#S....S#", "#.....S#", 1);
+        assert_eq!(
+            error(&few),
+            "the map has 3 spawn points, it needs at least 4"
+        );
+    }
+
+    #[test]
+    fn rejects_cut_off_floor() {
+        // one floor tile walled in all round
+        let cut_off = "\
+########
+#S....S#
+#......#
+#.###..#
+#.#.#..#
+#.###..#
+#S....S#
+########
+";
+        assert_eq!(
+            error(cut_off),
+            "the floor at row 5, column 4 can't be reached from the spawn points"
+        );
+    }
+
+    #[test]
+    fn rejects_maps_too_small_or_empty() {
+        assert_eq!(error("\n\n"), "the map is empty");
+        let small = "#####\n#SS.#\n#SS.#\n#####\n";
+        assert_eq!(
+            error(small),
+            "the map is 5 tiles wide, it must be between 8 and 128"
+        );
+    }
+
+    #[test]
+    fn bundled_maps_are_valid() {
+        for text in [
+            include_str!("../../maps/arena.txt"),
+            include_str!("../../maps/cross.txt"),
+        ] {
+            let map = parse_map(text).unwrap();
+            let (width, height) = (map.maze.width, map.maze.height);
+            assert_eq!(map.stats.problem(width, height), None);
+        }
+    }
+
+    #[test]
+    fn only_the_maps_asked_for_are_loaded() {
+        let dir = std::env::temp_dir().join(format!("maze-wars-maps-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("room.txt"), ROOM).unwrap();
+        std::fs::write(dir.join("broken.txt"), "#S.\n").unwrap();
+
+        let maps = load_maps(&dir, ["room", "room"]);
+        let broken = load_maps(&dir, ["broken"]).unwrap_err();
+        let missing = load_maps(&dir, ["nowhere"]).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(maps.unwrap().keys().collect::<Vec<_>>(), ["room"]);
+        assert!(broken.starts_with("bad map"), "{}", broken);
+        assert_eq!(missing, format!("no map \"nowhere\" in {}", dir.display()));
+    }
+}
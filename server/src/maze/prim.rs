@@ -0,0 +1,44 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use super::{
+    carve_passage, cell_count, cell_neighbours, loop_count, open_loops, walled, Grid, Level,
+    MazeGenerator,
+};
+
+/// Randomized Prim's algorithm: the maze grows outwards from one cell, which
+/// gives lots of short branches.
+pub struct Prim;
+
+impl MazeGenerator for Prim {
+    fn generate(&self, width: usize, height: usize, level: &Level, rng: &mut StdRng) -> Grid {
+        let mut maze = walled(width, height);
+        let cells = cell_count(width, height);
+        let mut in_maze = vec![vec![false; cells.0]; cells.1];
+
+        let start = (rng.gen_range(0..cells.0), rng.gen_range(0..cells.1));
+        in_maze[start.1][start.0] = true;
+        // passages that would lead from the maze to a cell outside it
+        let mut frontier: Vec<_> = cell_neighbours(start, cells)
+            .into_iter()
+            .map(|next| (start, next))
+            .collect();
+
+        while !frontier.is_empty() {
+            let (from, to) = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+            if in_maze[to.1][to.0] {
+                continue;
+            }
+            in_maze[to.1][to.0] = true;
+            carve_passage(&mut maze, from, to);
+            for next in cell_neighbours(to, cells) {
+                if !in_maze[next.1][next.0] {
+                    frontier.push((to, next));
+                }
+            }
+        }
+
+        open_loops(&mut maze, loop_count(cells.0 * cells.1, level), rng);
+        maze
+    }
+}
@@ -0,0 +1,85 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use super::{walled, Grid, Level, MazeGenerator};
+use maze_wars_protocol::EMPTY;
+
+/// Rectangular rooms joined one after another by L-shaped corridors, plus
+/// shortcuts between random pairs of rooms on open levels.
+pub struct Rooms;
+
+struct Room {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Room {
+    fn center(&self) -> (usize, usize) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    /// Whether the rooms overlap or have less than a wall between them.
+    fn touches(&self, other: &Room) -> bool {
+        self.x <= other.x + other.width
+            && other.x <= self.x + self.width
+            && self.y <= other.y + other.height
+            && other.y <= self.y + self.height
+    }
+}
+
+impl MazeGenerator for Rooms {
+    fn generate(&self, width: usize, height: usize, level: &Level, rng: &mut StdRng) -> Grid {
+        let mut maze = walled(width, height);
+        let max_side = (width.min(height) / 3).clamp(3, 8);
+        let wanted = (width * height / 60).max(2);
+
+        let mut rooms: Vec<Room> = Vec::new();
+        for _ in 0..wanted * 10 {
+            if rooms.len() == wanted {
+                break;
+            }
+            let room_width = rng.gen_range(3..=max_side);
+            let room_height = rng.gen_range(3..=max_side);
+            let room = Room {
+                x: rng.gen_range(1..=width - 1 - room_width),
+                y: rng.gen_range(1..=height - 1 - room_height),
+                width: room_width,
+                height: room_height,
+            };
+            if rooms.iter().any(|other| room.touches(other)) {
+                continue;
+            }
+            for row in &mut maze[room.y..room.y + room.height] {
+                row[room.x..room.x + room.width].fill(EMPTY);
+            }
+            rooms.push(room);
+        }
+
+        let mut links: Vec<(usize, usize)> = (1..rooms.len()).map(|i| (i - 1, i)).collect();
+        let shortcuts = (rooms.len() as f32 * level.openness).round() as usize;
+        for _ in 0..shortcuts {
+            links.push((rng.gen_range(0..rooms.len()), rng.gen_range(0..rooms.len())));
+        }
+        for (a, b) in links {
+            let (from, to) = (rooms[a].center(), rooms[b].center());
+            // turn the corner either way round
+            let corner = if rng.gen() {
+                (to.0, from.1)
+            } else {
+                (from.0, to.1)
+            };
+            dig(&mut maze, from, corner);
+            dig(&mut maze, corner, to);
+        }
+        maze
+    }
+}
+
+/// Clears a straight horizontal or vertical line of tiles.
+fn dig(maze: &mut Grid, (x0, y0): (usize, usize), (x1, y1): (usize, usize)) {
+    for row in &mut maze[y0.min(y1)..=y0.max(y1)] {
+        row[x0.min(x1)..=x0.max(x1)].fill(EMPTY);
+    }
+}
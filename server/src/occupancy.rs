@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use macroquad::prelude as mq;
+use maze_wars_protocol::{walks_into, Player, PlayerId, Position, PLAYER_RADIUS, TILE_SIZE};
+
+/// How close a shot has to pass to a player's position to hit them, in
+/// tiles.
+const HIT_RADIUS: f32 = PLAYER_RADIUS;
+
+/// Where every player stands. Kept apart from the maze, which only holds
+/// terrain, so a shot or a move can tell exactly who is in the way.
+#[derive(Debug, Default)]
+pub struct Occupancy {
+    players: HashMap<PlayerId, Position>,
+}
+
+impl Occupancy {
+    pub fn new(players: &[Player]) -> Self {
+        Occupancy {
+            players: players.iter().map(|p| (p.id, p.pos)).collect(),
+        }
+    }
+
+    /// Whether `id` moving from `from` to `to` would walk into another
+    /// player. Players already overlapping can still move apart.
+    pub fn blocks(&self, id: PlayerId, from: Position, to: Position) -> bool {
+        self.players
+            .iter()
+            .filter(|(&other, _)| other != id)
+            .any(|(_, &pos)| walks_into(from, to, pos))
+    }
+
+    /// Moves a player on the index. One taken off it stays off.
+    pub fn relocate(&mut self, id: PlayerId, to: Position) {
+        if let Some(pos) = self.players.get_mut(&id) {
+            *pos = to;
+        }
+    }
+
+    /// The first player other than `shooter` that a shot from `from` along
+    /// the unit vector `direction` hits within `reach` world units.
+    pub fn hit_by(
+        &self,
+        shooter: PlayerId,
+        from: Position,
+        direction: mq::Vec2,
+        reach: f32,
+    ) -> Option<PlayerId> {
+        let radius = HIT_RADIUS * TILE_SIZE;
+        let from = mq::Vec2::new(from.x, from.y);
+        self.players
+            .iter()
+            .filter(|(&id, _)| id != shooter)
+            .filter_map(|(&id, pos)| {
+                let to_player = mq::Vec2::new(pos.x, pos.y) - from;
+                // how far along the shot it passes closest to them, and how close
+                let along = to_player.dot(direction);
+                let miss_squared = to_player.length_squared() - along * along;
+                if along < 0.0 || miss_squared > radius * radius {
+                    return None;
+                }
+                let entry = (along - (radius * radius - miss_squared).sqrt()).max(0.0);
+                (entry <= reach).then_some((entry, id))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)))
+            .map(|(_, id)| id)
+    }
+
+    /// Takes a player off the index, such as one just shot.
+    pub fn remove(&mut self, id: PlayerId) {
+        self.players.remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maze_wars_protocol::testing::at;
+
+    fn occupancy(players: &[(PlayerId, Position)]) -> Occupancy {
+        let players: Vec<Player> = players
+            .iter()
+            .map(|&(id, pos)| Player::new(pos, id, format!("player{id}")))
+            .collect();
+        Occupancy::new(&players)
+    }
+
+    const EAST: mq::Vec2 = mq::Vec2::new(1.0, 0.0);
+
+    #[test]
+    fn hits_within_the_radius_and_misses_just_outside() {
+        let reach = 10.0 * TILE_SIZE;
+        let shooter = at(1.5, 1.5);
+        let inside = occupancy(&[(0, shooter), (1, at(4.5, 1.5 + HIT_RADIUS * 0.95))]);
+        assert_eq!(inside.hit_by(0, shooter, EAST, reach), Some(1));
+        let outside = occupancy(&[(0, shooter), (1, at(4.5, 1.5 + HIT_RADIUS * 1.05))]);
+        assert_eq!(outside.hit_by(0, shooter, EAST, reach), None);
+    }
+
+    #[test]
+    fn the_nearest_player_is_hit() {
+        let shooter = at(1.5, 1.5);
+        let players = occupancy(&[(0, shooter), (1, at(5.5, 1.5)), (2, at(3.5, 1.6))]);
+        assert_eq!(players.hit_by(0, shooter, EAST, 10.0 * TILE_SIZE), Some(2));
+    }
+
+    #[test]
+    fn nobody_behind_the_shooter_or_past_the_reach_is_hit() {
+        let shooter = at(3.5, 1.5);
+        let players = occupancy(&[(0, shooter), (1, at(1.5, 1.5)), (2, at(6.5, 1.5))]);
+        // the wall at 2.5 tiles stops the shot before player 2's circle
+        assert_eq!(players.hit_by(0, shooter, EAST, 2.5 * TILE_SIZE), None);
+        assert_eq!(players.hit_by(0, shooter, EAST, 3.0 * TILE_SIZE), Some(2));
+    }
+
+    #[test]
+    fn a_removed_player_is_not_hit() {
+        let shooter = at(1.5, 1.5);
+        let mut players = occupancy(&[(0, shooter), (1, at(3.5, 1.5))]);
+        players.remove(1);
+        assert_eq!(players.hit_by(0, shooter, EAST, 10.0 * TILE_SIZE), None);
+    }
+}
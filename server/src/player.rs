@@ -1,167 +1,134 @@
-use crate::{BREAKABLE, EMPTY, MAZE_HEIGHT, MAZE_WIDTH, PLAYER, TILE_SIZE, WALL};
+use crate::hitscan::trace;
+use crate::occupancy::Occupancy;
 use macroquad::prelude as mq;
-use serde::{Deserialize, Serialize};
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Position {
-    pub(crate) x: f32,
-    pub(crate) y: f32,
+use maze_wars_protocol::{
+    walk, Direction, Maze, MoveKeys, Player, PlayerId, BREAKABLE, EMPTY, MAX_LOOK_VERTICAL,
+    TILE_SIZE,
+};
+use std::f32::consts::TAU;
+
+pub trait PlayerInput {
+    fn walk(
+        &mut self,
+        keys: MoveKeys,
+        angle: f32,
+        distance: f32,
+        maze: &Maze,
+        occupancy: &mut Occupancy,
+    );
+    fn look(&mut self, angle: f32, vertical: f32);
+    fn shoot(
+        &mut self,
+        angle: f32,
+        maze: &mut Maze,
+        occupancy: &mut Occupancy,
+        shot_range: u32,
+    ) -> Option<PlayerId>;
 }
 
-impl Position {
-    fn new(x: f32, y: f32) -> Self {
-        Self { x, y }
+impl PlayerInput for Player {
+    /// Moves the player `distance` world units the way `keys` point while
+    /// facing `angle`, for one tick of holding them.
+    fn walk(
+        &mut self,
+        keys: MoveKeys,
+        angle: f32,
+        distance: f32,
+        maze: &Maze,
+        occupancy: &mut Occupancy,
+    ) {
+        face(self, angle);
+        let id = self.id;
+        self.pos = walk(self.pos, keys, self.angle, distance, maze, |from, to| {
+            occupancy.blocks(id, from, to)
+        });
+        occupancy.relocate(id, self.pos);
     }
-}
 
-impl Default for Position {
-    fn default() -> Self {
-        Self { x: 1.0, y: 0.0 }
+    /// Looks `angle` around and `vertical` up or down. The client turns
+    /// freely, within what a player could look at.
+    fn look(&mut self, angle: f32, vertical: f32) {
+        face(self, angle);
+        self.angle_vertical = vertical.clamp(-MAX_LOOK_VERTICAL, MAX_LOOK_VERTICAL);
     }
-}
 
-type Direction = Position;
-
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct Player {
-    pub(crate) id: usize,
-    pub(crate) pos: Position,
-    direction: Direction,
-    angle: f32,          // in radians
-    angle_vertical: f32, // in radians
-    pub(crate) action: String,
-    pub(crate) name: String,
-    pub(crate) score: u32,
-}
-impl Player {
-    pub(crate) fn new(pos: Position, id: usize, name: String) -> Self {
-        Self {
-            id,
-            pos,
-            direction: Direction::default(),
-            angle: 0.0,
-            angle_vertical: 0.0,
-            action: String::from(""),
-            name,
-            score: 0,
+    /// Fires along `angle`. Returns the player this one shot, if any.
+    fn shoot(
+        &mut self,
+        angle: f32,
+        maze: &mut Maze,
+        occupancy: &mut Occupancy,
+        shot_range: u32,
+    ) -> Option<PlayerId> {
+        face(self, angle);
+        let direction = mq::Vec2::new(self.angle.cos(), self.angle.sin());
+        let range = shot_range as f32 * TILE_SIZE;
+        let (reach, wall) = trace(maze, self.pos, direction, range);
+
+        if let Some(victim) = occupancy.hit_by(self.id, self.pos, direction, reach) {
+            // the victim is out of the way until they respawn
+            occupancy.remove(victim);
+            return Some(victim);
         }
-    }
-    pub fn touching_wall(&mut self, move_vec: mq::Vec2, maze: &mut [u8], moved: &mut bool) {
-        let new_x = self.pos.x + TILE_SIZE * move_vec.x;
-        let new_y = self.pos.y + TILE_SIZE * move_vec.y;
-
-        let map_x = (new_x / TILE_SIZE) as usize;
-        let map_y = (new_y / TILE_SIZE) as usize;
-        let map_index = map_y * MAZE_WIDTH + map_x;
-
-        if maze[map_index] == EMPTY {
-            //set the current positions tile to 0
-            let current_map_x = (self.pos.x / TILE_SIZE) as usize;
-            let current_map_y = (self.pos.y / TILE_SIZE) as usize;
-            let current_map_index = current_map_y * MAZE_WIDTH + current_map_x;
-            maze[current_map_index] = 0;
-            self.pos.x = new_x;
-            self.pos.y = new_y;
-
-            maze[map_index] = PLAYER;
-            self.action = String::from("");
-            *moved = true;
+        if let Some(index) = wall.and_then(|(x, y)| maze.index(x, y)) {
+            if maze.tiles[index] == BREAKABLE {
+                maze.tiles[index] = EMPTY;
+            }
         }
+        None
     }
+}
 
-    pub fn input(&mut self, maze: &mut [u8], moved: &mut bool) -> Option<u32> {
-        if self.action == "left" {
-            self.angle -= std::f32::consts::FRAC_PI_2;
-            self.action = String::from("");
-            *moved = true;
-        }
-        if self.action == "right" {
-            self.angle += std::f32::consts::FRAC_PI_2;
-            self.action = String::from("");
-            *moved = true;
-        }
-
-        if self.action == "shoot" {
-            // Convert player position to grid coordinates
-            let grid_x = (self.pos.x / TILE_SIZE).floor() as usize;
-            let grid_y = (self.pos.y / TILE_SIZE).floor() as usize;
-
-            // Determine direction to step through the map based on angle
-            let step_x = self.angle.cos().round() as isize; // Round to ensure we move strictly in grid directions
-            let step_y = self.angle.sin().round() as isize;
-
-            // Initialize variables for iteration
-            let mut current_x = grid_x as isize + step_x;
-            let mut current_y = grid_y as isize + step_y;
-            const MAX_RANGE: i32 = 6;
-            let mut distance = 0;
-
-            while current_x >= 0
-                && current_x < MAZE_WIDTH as isize
-                && current_y >= 0
-                && current_y < MAZE_HEIGHT as isize
-            {
-                if distance > MAX_RANGE {
-                    break;
-                }
-
-                let idx = (current_y * MAZE_WIDTH as isize + current_x) as usize;
-                // if idx is 2 return none and break
-                if maze[idx] == WALL {
-                    break;
-                }
-
-                if maze[idx] == PLAYER || maze[idx] == BREAKABLE {
-                    if maze[idx] == PLAYER {
-                        self.score += 1;
-                    }
-                    maze[idx] = EMPTY;
-                    *moved = true;
-                    self.action = String::from("");
-                    return Some(idx as u32);
-                }
-                // Move to the next tile in the direction
-                current_x += step_x;
-                current_y += step_y;
-                distance += 1;
-            }
-
-            self.action = String::from(""); // Clear action after processing
-        }
+/// Turns `player` to `angle`, wrapped into a single turn.
+fn face(player: &mut Player, angle: f32) {
+    player.angle = angle.rem_euclid(TAU);
+    player.direction = Direction::new(player.angle.cos(), player.angle.sin());
+}
 
-        self.direction = Direction::new(self.angle.cos(), self.angle.sin());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maze_wars_protocol::testing::{at, maze};
+    use maze_wars_protocol::WALL;
 
-        let mut move_vec = mq::Vec2::new(0.0, 0.0);
-        // Updated so you move one tile at a time
+    /// One corridor with a breakable wall at its end.
+    fn corridor() -> Maze {
+        maze(&["########", "#.....%#", "########"])
+    }
 
-        if self.action == "W" {
-            move_vec = mq::Vec2::new(self.direction.x, self.direction.y);
-        }
-        if self.action == "S" {
-            move_vec = mq::Vec2::new(-self.direction.x, -self.direction.y);
-        }
-        if self.action == "D" {
-            move_vec = mq::Vec2::new(-self.direction.y, self.direction.x);
-        }
-        if self.action == "A" {
-            move_vec = mq::Vec2::new(self.direction.y, -self.direction.x);
-        }
+    fn player(id: PlayerId, x: f32) -> Player {
+        Player::new(at(x, 1.5), id, format!("player{id}"))
+    }
 
-        if move_vec.length() > 0.0 {
-            self.touching_wall(move_vec, maze, moved);
-        }
+    #[test]
+    fn a_shot_that_hits_a_player_leaves_the_wall_behind_them() {
+        let mut maze = corridor();
+        let (mut shooter, target) = (player(0, 1.5), player(1, 4.5));
+        let mut occupancy = Occupancy::new(&[shooter.clone(), target]);
+        assert_eq!(shooter.shoot(0.0, &mut maze, &mut occupancy, 10), Some(1));
+        assert_eq!(maze.get(6, 1), Some(BREAKABLE));
+    }
 
-        if self.pos.x < 0.0 {
-            self.pos.x = 0.0;
-        } else if self.pos.x > MAZE_WIDTH as f32 * TILE_SIZE {
-            self.pos.x = MAZE_WIDTH as f32 * TILE_SIZE;
-        }
+    #[test]
+    fn a_shot_that_hits_nobody_breaks_the_wall() {
+        let mut maze = corridor();
+        let mut shooter = player(0, 1.5);
+        let mut occupancy = Occupancy::new(&[shooter.clone()]);
+        assert_eq!(shooter.shoot(0.0, &mut maze, &mut occupancy, 10), None);
+        assert_eq!(maze.get(6, 1), Some(EMPTY));
+        // the wall behind it is solid
+        assert_eq!(shooter.shoot(0.0, &mut maze, &mut occupancy, 10), None);
+        assert_eq!(maze.get(7, 1), Some(WALL));
+    }
 
-        if self.pos.y < 0.0 {
-            self.pos.y = 0.0;
-        } else if self.pos.y > MAZE_HEIGHT as f32 * TILE_SIZE {
-            self.pos.y = MAZE_HEIGHT as f32 * TILE_SIZE;
-        }
-        None
+    #[test]
+    fn a_breakable_wall_shields_the_player_behind_it_once() {
+        let mut maze = corridor();
+        maze.tiles[8 + 4] = BREAKABLE;
+        let (mut shooter, target) = (player(0, 1.5), player(1, 5.5));
+        let mut occupancy = Occupancy::new(&[shooter.clone(), target]);
+        assert_eq!(shooter.shoot(0.0, &mut maze, &mut occupancy, 10), None);
+        assert_eq!(maze.get(4, 1), Some(EMPTY));
+        assert_eq!(shooter.shoot(0.0, &mut maze, &mut occupancy, 10), Some(1));
     }
 }
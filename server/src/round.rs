@@ -0,0 +1,65 @@
+use maze_wars_protocol::RoundPhase;
+
+/// Where the server is in the Playing -> Intermission -> Playing cycle.
+pub enum RoundState {
+    Playing,
+    Intermission { winner: String, ends_at_tick: u32 },
+}
+
+impl RoundState {
+    pub fn is_playing(&self) -> bool {
+        matches!(self, RoundState::Playing)
+    }
+
+    /// The phase as clients see it, with the countdown in whole seconds.
+    pub fn phase(&self, tick: u32, tick_rate: u32) -> RoundPhase {
+        match self {
+            RoundState::Playing => RoundPhase::Playing,
+            RoundState::Intermission {
+                winner,
+                ends_at_tick,
+            } => RoundPhase::Intermission {
+                winner: winner.clone(),
+                seconds_left: ends_at_tick.saturating_sub(tick).div_ceil(tick_rate),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds_left(tick: u32) -> u32 {
+        let intermission = RoundState::Intermission {
+            winner: String::from("winner"),
+            ends_at_tick: 100,
+        };
+        match intermission.phase(tick, 30) {
+            RoundPhase::Intermission { seconds_left, .. } => seconds_left,
+            RoundPhase::Playing => panic!("playing during the intermission"),
+        }
+    }
+
+    #[test]
+    fn the_countdown_rounds_up_to_whole_seconds() {
+        assert_eq!(seconds_left(40), 2);
+        assert_eq!(seconds_left(41), 2);
+        assert_eq!(seconds_left(70), 1);
+        assert_eq!(seconds_left(99), 1);
+        assert_eq!(seconds_left(100), 0);
+        // a late tick doesn't count below zero
+        assert_eq!(seconds_left(130), 0);
+    }
+
+    #[test]
+    fn playing_is_playing() {
+        assert!(RoundState::Playing.is_playing());
+        assert_eq!(RoundState::Playing.phase(7, 30), RoundPhase::Playing);
+        let intermission = RoundState::Intermission {
+            winner: String::from("winner"),
+            ends_at_tick: 100,
+        };
+        assert!(!intermission.is_playing());
+    }
+}
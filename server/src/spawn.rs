@@ -0,0 +1,135 @@
+use maze_wars_protocol::{Maze, BREAKABLE, EMPTY, WALL};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+
+/// Tile coordinates, column first.
+pub type Tile = (usize, usize);
+
+/// Spawns are picked at random among this many of the best tiles, so the
+/// safest tile isn't also the most predictable one.
+const SPAWN_CHOICES: usize = 3;
+
+/// Most tiles weighed for one spawn; big open mazes are sampled instead.
+const MAX_CANDIDATES: usize = 256;
+
+/// Picks a free tile for a player to appear on, given where the other
+/// players are.
+///
+/// Free spawn points of the map come first, then any empty tile nobody
+/// stands on. Tiles nobody can see are preferred, then the ones furthest
+/// from the nearest player. `None` when there is no free tile at all.
+pub fn choose_spawn(maze: &Maze, spawns: &[Tile], others: &[Tile]) -> Option<Tile> {
+    let free = |tile: &Tile| maze.get(tile.0, tile.1) == Some(EMPTY) && !others.contains(tile);
+    let mut candidates: Vec<Tile> = spawns.iter().copied().filter(free).collect();
+    if candidates.is_empty() {
+        candidates = (0..maze.height)
+            .flat_map(|y| (0..maze.width).map(move |x| (x, y)))
+            .filter(free)
+            .collect();
+    }
+
+    // shuffled so equally good tiles don't always favour the top left
+    let mut rng = thread_rng();
+    candidates.shuffle(&mut rng);
+    candidates.truncate(MAX_CANDIDATES);
+
+    let mut scored: Vec<(bool, f32, Tile)> = candidates
+        .into_iter()
+        .map(|tile| {
+            let seen = others
+                .iter()
+                .any(|&other| in_line_of_sight(maze, tile, other));
+            let nearest = others
+                .iter()
+                .map(|&other| distance(tile, other))
+                .fold(f32::INFINITY, f32::min);
+            (seen, nearest, tile)
+        })
+        .collect();
+    scored.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.total_cmp(&a.1)));
+    // never trade a hidden tile for a visible one just to add variety
+    let best_seen = scored.first()?.0;
+    scored.retain(|&(seen, _, _)| seen == best_seen);
+    scored.truncate(SPAWN_CHOICES);
+    scored.choose(&mut rng).map(|&(_, _, tile)| tile)
+}
+
+fn distance((ax, ay): Tile, (bx, by): Tile) -> f32 {
+    (ax as f32 - bx as f32).hypot(ay as f32 - by as f32)
+}
+
+/// Whether a straight line between the centres of two tiles is clear of
+/// walls and breakables.
+fn in_line_of_sight(maze: &Maze, from: Tile, to: Tile) -> bool {
+    let (x0, y0) = (from.0 as f32 + 0.5, from.1 as f32 + 0.5);
+    let (x1, y1) = (to.0 as f32 + 0.5, to.1 as f32 + 0.5);
+    // a few samples per tile crossed, so no corner gets skipped
+    let steps = (distance(from, to) * 4.0).ceil() as usize;
+    (1..steps).all(|step| {
+        let t = step as f32 / steps as f32;
+        let x = (x0 + (x1 - x0) * t) as usize;
+        let y = (y0 + (y1 - y0) * t) as usize;
+        !matches!(maze.get(x, y), Some(WALL) | Some(BREAKABLE))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maze_wars_protocol::testing::maze;
+
+    /// Two corridors with a wall between them, joined on the right.
+    fn corridors() -> Maze {
+        maze(&["#######", "#.....#", "#####.#", "#.....#", "#######"])
+    }
+
+    #[test]
+    fn a_hidden_tile_beats_a_visible_one_further_away() {
+        let maze = corridors();
+        // (5, 1) is down the corridor from the other player, (1, 3) behind the wall
+        let spawns = [(5, 1), (1, 3)];
+        for _ in 0..20 {
+            assert_eq!(choose_spawn(&maze, &spawns, &[(1, 1)]), Some((1, 3)));
+        }
+    }
+
+    #[test]
+    fn free_spawn_points_come_first() {
+        let maze = corridors();
+        // right next to the other player, but the only spawn point
+        for _ in 0..20 {
+            assert_eq!(choose_spawn(&maze, &[(2, 1)], &[(1, 1)]), Some((2, 1)));
+        }
+    }
+
+    #[test]
+    fn taken_spawn_points_fall_back_to_any_free_tile() {
+        let maze = corridors();
+        for _ in 0..20 {
+            let tile = choose_spawn(&maze, &[(1, 1)], &[(1, 1)]).unwrap();
+            assert_ne!(tile, (1, 1));
+            assert_eq!(maze.get(tile.0, tile.1), Some(EMPTY));
+        }
+    }
+
+    #[test]
+    fn the_furthest_hidden_tiles_are_chosen() {
+        let maze = corridors();
+        // the wall hides the bottom corridor and the way round from (1, 1),
+        // the three tiles at its far end are the furthest away
+        for _ in 0..20 {
+            let tile = choose_spawn(&maze, &[], &[(1, 1)]).unwrap();
+            assert!([(5, 3), (5, 2), (4, 3)].contains(&tile), "{:?}", tile);
+        }
+    }
+
+    #[test]
+    fn a_full_maze_has_no_spawn() {
+        let maze = corridors();
+        let everyone: Vec<Tile> = (0..maze.height)
+            .flat_map(|y| (0..maze.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| maze.get(x, y) == Some(EMPTY))
+            .collect();
+        assert_eq!(choose_spawn(&maze, &[(1, 1)], &everyone), None);
+    }
+}